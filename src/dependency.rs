@@ -0,0 +1,263 @@
+use crate::download::{下載參數, 下載配方包};
+use crate::install::讀取配方文件;
+use crate::package::配方包;
+use crate::recipe::配方名片;
+
+use anyhow::bail;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// 下載配方及其依賴, 返回安裝順序: 被依賴的配方在前.
+pub fn 下載配方及依賴(
+    衆配方: &[配方名片],
+    參數: &下載參數,
+    工作場地: &Path,
+) -> anyhow::Result<Vec<配方名片>> {
+    let 安裝順序 = 排定安裝順序(衆配方, |一批配方| {
+        let 一批配方 = 參數.按需鎖定版本(一批配方.to_vec(), 工作場地)?;
+        下載配方包(&一批配方, 參數, 工作場地)?;
+        一批配方
+            .iter()
+            .map(|配方| {
+                讀取配方文件(配方, 工作場地)?
+                    .map(|配方文件| 配方文件.衆依賴())
+                    .unwrap_or(Ok(vec![]))
+            })
+            .collect()
+    })?;
+    參數.按需鎖定版本(安裝順序, 工作場地)
+}
+
+/// 逐批下載新發現的配方, 讀出其依賴, 直到沒有新的配方; 再按依賴關係排序.
+///
+/// 同一倉庫只能有一個版本: 未指定版本的配方隨指定了版本的;
+/// 指定了不同版本則報錯. 倉庫按配方包目錄區分, 如 `foo` 和
+/// `https://github.com/rime/rime-foo` 是同一倉庫.
+fn 排定安裝順序<F>(
+    衆配方: &[配方名片],
+    mut 下載並讀取依賴: F,
+) -> anyhow::Result<Vec<配方名片>>
+where
+    F: FnMut(&[配方名片]) -> anyhow::Result<Vec<Vec<配方名片>>>,
+{
+    let mut 倉庫版本 = HashMap::<PathBuf, Option<String>>::new();
+    let mut 依賴關係 = HashMap::<配方名片, Vec<配方名片>>::new();
+    let mut 已下載 = HashSet::<配方名片>::new();
+    let mut 待下載 = vec![];
+    for 配方 in 衆配方 {
+        登記版本(&mut 倉庫版本, 配方, None)?;
+        if !待下載.contains(&配方.不論版本()) {
+            待下載.push(配方.不論版本());
+        }
+    }
+    while !待下載.is_empty() {
+        let 一批配方 = 待下載
+            .drain(..)
+            .map(|配方| 定版(&配方, &倉庫版本))
+            .collect::<Vec<_>>();
+        已下載.extend(一批配方.iter().map(配方名片::不論版本));
+        let 衆依賴 = 下載並讀取依賴(&一批配方)?;
+        for (配方, 依賴) in 一批配方.iter().zip(衆依賴) {
+            let mut 衆依賴配方 = vec![];
+            for 依賴配方 in &依賴 {
+                let 版本有變 = 登記版本(&mut 倉庫版本, 依賴配方, Some(配方))?;
+                let 依賴配方 = 依賴配方.不論版本();
+                if 版本有變 {
+                    // 要按新的版本重新下載
+                    已下載.remove(&依賴配方);
+                }
+                if !已下載.contains(&依賴配方) && !待下載.contains(&依賴配方) {
+                    待下載.push(依賴配方.clone());
+                }
+                衆依賴配方.push(依賴配方);
+            }
+            依賴關係.insert(配方.不論版本(), 衆依賴配方);
+        }
+    }
+
+    let mut 安裝順序 = vec![];
+    let mut 路過 = vec![];
+    for 配方 in 衆配方 {
+        深度優先(&配方.不論版本(), &依賴關係, &mut 路過, &mut 安裝順序)?;
+    }
+    Ok(安裝順序.iter().map(|配方| 定版(配方, &倉庫版本)).collect())
+}
+
+/// 記下配方所在倉庫的版本. 版本有變則返回 true.
+fn 登記版本(
+    倉庫版本: &mut HashMap<PathBuf, Option<String>>,
+    配方: &配方名片,
+    依賴者: Option<&配方名片>,
+) -> anyhow::Result<bool> {
+    let 倉庫 = 配方.倉庫名片();
+    match (倉庫版本.get(&包目錄(配方)), &配方.版本) {
+        (None, 版本) => {
+            倉庫版本.insert(包目錄(配方), 版本.clone());
+            Ok(false)
+        }
+        (Some(_), None) => Ok(false),
+        (Some(None), Some(版本)) => {
+            倉庫版本.insert(包目錄(配方), Some(版本.clone()));
+            Ok(true)
+        }
+        (Some(Some(既定)), Some(版本)) if 既定 == 版本 => Ok(false),
+        (Some(Some(既定)), Some(版本)) => match 依賴者 {
+            Some(依賴者) => bail!(
+                "版本衝突: {} 要求 {} 的版本 {}, 而已選定版本 {}",
+                依賴者,
+                倉庫,
+                版本,
+                既定
+            ),
+            None => bail!("版本衝突: {} 同時指定了版本 {} 和 {}", 倉庫, 既定, 版本),
+        },
+    }
+}
+
+fn 定版(配方: &配方名片, 倉庫版本: &HashMap<PathBuf, Option<String>>) -> 配方名片 {
+    配方名片 {
+        版本: 倉庫版本.get(&包目錄(配方)).cloned().flatten(),
+        ..配方.clone()
+    }
+}
+
+fn 包目錄(配方: &配方名片) -> PathBuf {
+    配方包 {
+        配方: 配方.clone(),
+        倉庫域名: None,
+    }
+    .相對路徑()
+}
+
+fn 深度優先(
+    配方: &配方名片,
+    依賴關係: &HashMap<配方名片, Vec<配方名片>>,
+    路過: &mut Vec<配方名片>,
+    安裝順序: &mut Vec<配方名片>,
+) -> anyhow::Result<()> {
+    if 安裝順序.contains(配方) {
+        return Ok(());
+    }
+    if let Some(位置) = 路過.iter().position(|路過的| 路過的 == 配方) {
+        let 環 = 路過[位置..]
+            .iter()
+            .chain([配方])
+            .map(配方名片::to_string)
+            .collect::<Vec<_>>();
+        bail!("循環依賴: {}", 環.join(" -> "));
+    }
+    路過.push(配方.clone());
+    for 依賴 in 依賴關係.get(配方).into_iter().flatten() {
+        深度優先(依賴, 依賴關係, 路過, 安裝順序)?;
+    }
+    路過.pop();
+    安裝順序.push(配方.clone());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::配方;
+
+    /// 按給定的依賴關係排序, 並記下每批下載的配方.
+    fn 排序(
+        衆配方: &[&str],
+        依賴關係: &[(&str, &[&str])],
+    ) -> (anyhow::Result<Vec<String>>, Vec<Vec<String>>) {
+        let 依賴關係 = 依賴關係
+            .iter()
+            .map(|(名字, 依賴)| (*名字, 依賴.iter().map(|依賴| 配方(依賴)).collect()))
+            .collect::<HashMap<_, Vec<_>>>();
+        let mut 衆批次 = vec![];
+        let 結果 = 排定安裝順序(
+            &衆配方.iter().map(|source| 配方(source)).collect::<Vec<_>>(),
+            |一批配方| {
+                衆批次.push(一批配方.iter().map(配方名片::to_string).collect());
+                Ok(一批配方
+                    .iter()
+                    .map(|配方| {
+                        let 名字 = 配方.名字.trim_start_matches("rime-");
+                        依賴關係.get(名字).cloned().unwrap_or_default()
+                    })
+                    .collect())
+            },
+        )
+        .map(|順序| 順序.iter().map(配方名片::to_string).collect());
+        (結果, 衆批次)
+    }
+
+    #[test]
+    fn 測試安裝順序() {
+        let (結果, 衆批次) = 排序(
+            &["terra-pinyin", "luna-pinyin"],
+            &[
+                ("terra-pinyin", &["stroke", "prelude"]),
+                ("luna-pinyin", &["stroke", "essay", "prelude"]),
+                ("stroke", &["prelude"]),
+                ("essay", &[]),
+            ],
+        );
+        assert_eq!(
+            結果.unwrap(),
+            [
+                "rime/rime-prelude",
+                "rime/rime-stroke",
+                "rime/rime-terra-pinyin",
+                "rime/rime-essay",
+                "rime/rime-luna-pinyin",
+            ]
+        );
+        assert_eq!(衆批次.len(), 2);
+        assert_eq!(
+            衆批次[0],
+            ["rime/rime-terra-pinyin", "rime/rime-luna-pinyin"]
+        );
+    }
+
+    #[test]
+    fn 測試安裝順序_依賴指定版本() {
+        let (結果, 衆批次) = 排序(
+            &["prelude", "luna-pinyin"],
+            &[("luna-pinyin", &["prelude@1.0"])],
+        );
+        assert_eq!(
+            結果.unwrap(),
+            ["rime/rime-prelude@1.0", "rime/rime-luna-pinyin"]
+        );
+        // prelude 先按默認版本下載, 得知要求 1.0 後重新下載
+        assert_eq!(衆批次[1], ["rime/rime-prelude@1.0"]);
+    }
+
+    #[test]
+    fn 測試安裝順序_版本衝突() {
+        let (結果, _) = 排序(
+            &["prelude@2.0", "luna-pinyin"],
+            &[("luna-pinyin", &["prelude@1.0"])],
+        );
+        assert!(結果.unwrap_err().to_string().contains("版本衝突"));
+
+        let (結果, _) = 排序(&["prelude@2.0", "prelude@1.0"], &[]);
+        assert!(結果.unwrap_err().to_string().contains("版本衝突"));
+
+        // 同一倉庫的不同寫法
+        let (結果, _) = 排序(
+            &[
+                "prelude@2.0",
+                "https://github.com/rime/rime-prelude.git@1.0",
+            ],
+            &[],
+        );
+        assert!(結果.unwrap_err().to_string().contains("版本衝突"));
+    }
+
+    #[test]
+    fn 測試安裝順序_循環依賴() {
+        let (結果, _) = 排序(&["a"], &[("a", &["b"]), ("b", &["c"]), ("c", &["a"])]);
+        assert_eq!(
+            結果.unwrap_err().to_string(),
+            "循環依賴: rime/rime-a -> rime/rime-b -> rime/rime-c -> rime/rime-a"
+        );
+    }
+}
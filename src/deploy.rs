@@ -0,0 +1,290 @@
+use crate::install::列出文件;
+use crate::lock::鎖定文件名;
+use crate::manifest::清單文件名;
+use crate::rime_levers::引擎目錄;
+
+use anyhow::{anyhow, bail};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use structopt::StructOpt;
+
+/// 隨輸入法固件一同部署的用戶文件.
+const 用戶文件: &[&str] = &["*.yaml", "*.txt", "*.gram", "opencc/*.*"];
+/// 屬於工作場地本身, 不必部署. 前端有自己的安裝信息和用戶狀態.
+const 不部署的文件: &[&str] = &[
+    "installation.yaml",
+    "user.yaml",
+    "recipe.yaml",
+    "*.recipe.yaml",
+    清單文件名,
+    鎖定文件名,
+];
+
+#[derive(Debug, StructOpt)]
+pub struct 部署參數 {
+    /// 部署目標: ibus-rime, fcitx5-rime, fcitx-rime, 或前端數據目錄的路徑
+    target: 部署目標,
+    /// 前端的數據目錄, 不用默認位置
+    #[structopt(long, parse(from_os_str))]
+    frontend_dir: Option<PathBuf>,
+    /// 只列出要複製的文件, 不實際複製
+    #[structopt(long)]
+    dry_run: bool,
+    /// 部署後通知前端重新加載
+    #[structopt(long)]
+    reload: bool,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum 前端 {
+    Ibus,
+    Fcitx5,
+    Fcitx,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum 部署目標 {
+    前端(前端),
+    目錄(PathBuf),
+}
+
+const 衆前端: &[前端] = &[前端::Ibus, 前端::Fcitx5, 前端::Fcitx];
+
+impl 前端 {
+    fn 名稱(&self) -> &'static str {
+        match self {
+            前端::Ibus => "ibus-rime",
+            前端::Fcitx5 => "fcitx5-rime",
+            前端::Fcitx => "fcitx-rime",
+        }
+    }
+
+    /// 前端在 Linux 上的用戶數據目錄.
+    pub fn 數據目錄(&self) -> anyhow::Result<PathBuf> {
+        if !cfg!(target_os = "linux") {
+            bail!("{self} 在此平臺上沒有默認的數據目錄, 請用 --frontend-dir 指定");
+        }
+        let 主目錄 = || {
+            std::env::var_os("HOME").map(PathBuf::from).ok_or(anyhow!(
+                "未設置 HOME, 請用 --frontend-dir 指定前端的數據目錄"
+            ))
+        };
+        let xdg_目錄 = |變量, 缺省: &str| -> anyhow::Result<PathBuf> {
+            match std::env::var_os(變量) {
+                Some(目錄) if !目錄.is_empty() => Ok(PathBuf::from(目錄)),
+                _ => Ok(主目錄()?.join(缺省)),
+            }
+        };
+        Ok(match self {
+            前端::Ibus => xdg_目錄("XDG_CONFIG_HOME", ".config")?.join("ibus/rime"),
+            前端::Fcitx5 => xdg_目錄("XDG_DATA_HOME", ".local/share")?.join("fcitx5/rime"),
+            前端::Fcitx => xdg_目錄("XDG_CONFIG_HOME", ".config")?.join("fcitx/rime"),
+        })
+    }
+
+    /// 通知前端重新加載輸入法數據.
+    pub fn 重新加載(&self) -> anyhow::Result<()> {
+        let (程序, 參數) = match self {
+            前端::Ibus => ("ibus", "restart"),
+            前端::Fcitx5 => ("fcitx5-remote", "-r"),
+            前端::Fcitx => ("fcitx-remote", "-r"),
+        };
+        log::debug!("通知前端重新加載: {程序} {參數}");
+        let 狀態 = Command::new(程序)
+            .arg(參數)
+            .status()
+            .map_err(|e| anyhow!("無法運行 {程序}: {e}"))?;
+        if !狀態.success() {
+            bail!("{程序} {參數} 失敗: {狀態}");
+        }
+        log::info!("已通知 {self} 重新加載");
+        Ok(())
+    }
+}
+
+impl fmt::Display for 前端 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.名稱())
+    }
+}
+
+impl FromStr for 部署目標 {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(前端) = 衆前端.iter().find(|前端| 前端.名稱() == s) {
+            return Ok(部署目標::前端(*前端));
+        }
+        if s.contains(std::path::is_separator) || s.starts_with('.') {
+            return Ok(部署目標::目錄(PathBuf::from(s)));
+        }
+        let 可選 = 衆前端.iter().map(前端::名稱).collect::<Vec<_>>();
+        bail!(
+            "未知的前端: {s}. 可選 {}, 或者給出目錄路徑",
+            可選.join(", ")
+        )
+    }
+}
+
+impl 部署參數 {
+    /// 部署到的目錄. 目標本身是目錄時, 不能再用 `--frontend-dir`.
+    fn 目標路徑(&self) -> anyhow::Result<PathBuf> {
+        match (&self.target, &self.frontend_dir) {
+            (部署目標::目錄(路徑), Some(_)) => bail!(
+                "部署目標 {} 已是目錄, 不能同時指定 --frontend-dir",
+                路徑.display()
+            ),
+            (部署目標::目錄(路徑), None) => Ok(路徑.clone()),
+            (部署目標::前端(_), Some(路徑)) => Ok(路徑.clone()),
+            (部署目標::前端(前端), None) => 前端.數據目錄(),
+        }
+    }
+}
+
+/// 把工作場地中構建好的輸入法固件和用戶文件複製到前端的數據目錄.
+pub fn 部署輸入法固件(目錄: &引擎目錄, 參數: &部署參數) -> anyhow::Result<()> {
+    let 目標路徑 = 參數.目標路徑()?;
+    let 衆文件 = 列出部署文件(目錄, &目標路徑)?;
+    if 參數.dry_run {
+        for (來源, 目標) in &衆文件 {
+            println!("{} -> {}", 來源.display(), 目標.display());
+        }
+        return Ok(());
+    }
+    for (來源, 目標) in &衆文件 {
+        if let Some(上級) = 目標.parent() {
+            fs::create_dir_all(上級)?;
+        }
+        fs::copy(來源, 目標).map_err(|e| anyhow!("複製 {} 失敗: {e}", 來源.display()))?;
+        log::debug!("部署文件: {}", 目標.display());
+    }
+    log::info!(
+        "部署好了 {} 個文件. 目標: {}",
+        衆文件.len(),
+        目標路徑.display()
+    );
+    if 參數.reload {
+        match &參數.target {
+            部署目標::前端(前端) => 前端.重新加載()?,
+            部署目標::目錄(_) => log::warn!("部署到目錄時不知道該通知哪個前端, 未重新加載"),
+        }
+    }
+    Ok(())
+}
+
+/// 列出要部署的文件: 整備區中的全部文件放到目標的 build 目錄, 用戶文件照原樣放.
+fn 列出部署文件(
+    目錄: &引擎目錄, 目標路徑: &Path
+) -> anyhow::Result<Vec<(PathBuf, PathBuf)>> {
+    let 整備區 = 目錄
+        .整備區
+        .clone()
+        .unwrap_or_else(|| 目錄.用戶數據.join("build"));
+    if !整備區.is_dir() {
+        bail!(
+            "還沒有構建輸入法固件: {}. 請先運行 rime build",
+            整備區.display()
+        );
+    }
+    let mut 衆文件 = 列出文件(&整備區, &["**/*"], &[])?
+        .into_iter()
+        .map(|文件| (整備區.join(&文件), 目標路徑.join("build").join(文件)))
+        .collect::<Vec<_>>();
+    衆文件.extend(
+        列出文件(&目錄.用戶數據, 用戶文件, 不部署的文件)?
+            .into_iter()
+            .map(|文件| (目錄.用戶數據.join(&文件), 目標路徑.join(文件))),
+    );
+    Ok(衆文件)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use claims::assert_ok;
+    use std::fs::{create_dir_all, write};
+
+    #[test]
+    fn 測試部署目標() {
+        assert_eq!(
+            assert_ok!(部署目標::from_str("fcitx5-rime")),
+            部署目標::前端(前端::Fcitx5)
+        );
+        assert_eq!(
+            assert_ok!(部署目標::from_str("./rime")),
+            部署目標::目錄(PathBuf::from("./rime"))
+        );
+        assert_eq!(
+            assert_ok!(部署目標::from_str("/usr/share/rime-data")),
+            部署目標::目錄(PathBuf::from("/usr/share/rime-data"))
+        );
+        assert!(部署目標::from_str("fcitx5").is_err());
+    }
+
+    #[test]
+    fn 測試部署目標路徑() {
+        let 參數 = |衆參數: &[&str]| 部署參數::from_iter([&["deploy"], 衆參數].concat());
+        assert_eq!(
+            assert_ok!(參數(&["fcitx5-rime", "--frontend-dir", "/tmp/rime"]).目標路徑()),
+            PathBuf::from("/tmp/rime")
+        );
+        assert_eq!(
+            assert_ok!(參數(&["./rime"]).目標路徑()),
+            PathBuf::from("./rime")
+        );
+        assert!(參數(&["./rime", "--frontend-dir", "/tmp/rime"])
+            .目標路徑()
+            .is_err());
+    }
+
+    #[test]
+    fn 測試列出部署文件() {
+        let tmp_dir = assert_ok!(tempfile::tempdir());
+        let 工作場地 = tmp_dir.path();
+        let 目錄 = 引擎目錄::工作場地(工作場地);
+        let 目標路徑 = Path::new("/target");
+        assert!(列出部署文件(&目錄, 目標路徑).is_err());
+
+        assert_ok!(create_dir_all(工作場地.join("build/opencc")));
+        assert_ok!(create_dir_all(工作場地.join("pkg/rime/rime-foo")));
+        for 文件 in [
+            "build/default.yaml",
+            "build/foo.table.bin",
+            "build/opencc/t2s.ocd2",
+            "default.custom.yaml",
+            "foo.dict.yaml",
+            "installation.yaml",
+            "user.yaml",
+            "rime-recipes.yaml",
+            "rime-recipes.lock",
+            "pkg/rime/rime-foo/foo.dict.yaml",
+        ] {
+            assert_ok!(write(工作場地.join(文件), ""));
+        }
+
+        let 衆文件 = assert_ok!(列出部署文件(&目錄, 目標路徑));
+        assert_eq!(
+            衆文件
+                .iter()
+                .map(|(來源, 目標)| (
+                    來源.strip_prefix(工作場地).unwrap().to_path_buf(),
+                    目標.to_path_buf()
+                ))
+                .collect::<Vec<_>>(),
+            [
+                ("build/default.yaml", "/target/build/default.yaml"),
+                ("build/foo.table.bin", "/target/build/foo.table.bin"),
+                ("build/opencc/t2s.ocd2", "/target/build/opencc/t2s.ocd2"),
+                ("default.custom.yaml", "/target/default.custom.yaml"),
+                ("foo.dict.yaml", "/target/foo.dict.yaml"),
+            ]
+            .iter()
+            .map(|(來源, 目標)| (PathBuf::from(來源), PathBuf::from(目標)))
+            .collect::<Vec<_>>()
+        );
+    }
+}
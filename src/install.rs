@@ -1,6 +1,426 @@
-use crate::recipe::配方名片;
+use crate::download::核對倉庫版本;
+use crate::installed::{安裝記錄, 安裝記錄文件名};
+use crate::package::配方包;
+use crate::recipe::{補丁內容, 配方名片, 配方文件};
+use crate::rime_levers::引擎目錄;
 
-pub fn 安裝配方(配方: &配方名片 /*, 參數: 配方參數 */) -> anyhow::Result<()> {
+use anyhow::{anyhow, bail};
+use glob::Pattern;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// 配方未指明安裝哪些文件時, 按 plum 的慣例安裝這些.
+const 默認安裝文件: &[&str] = &["*.yaml", "*.txt", "*.gram", "opencc/*.*"];
+/// 不是輸入法數據, 不必安裝.
+const 排除文件: &[&str] = &["recipe.yaml", "*.recipe.yaml", "*.custom.yaml"];
+
+pub fn 安裝配方(配方: &配方名片, 目錄: &引擎目錄) -> anyhow::Result<()> {
     log::debug!("安裝配方: {配方}");
-    todo!("還沒做呢");
+    let 工作場地 = &目錄.用戶數據;
+    let 包 = 配方包 {
+        配方: 配方.clone(),
+        倉庫域名: None,
+    };
+    let 本地倉庫 = 包.本地路徑(工作場地);
+    if !本地倉庫.exists() {
+        bail!("配方包還沒下載: {}", 本地倉庫.display());
+    }
+    核對倉庫版本(&包, &本地倉庫)?;
+
+    let 配方文件 = 讀取配方文件(配方, 工作場地)?;
+    if let Some(說明) = 配方文件.as_ref().map(|配方文件| &配方文件.說明) {
+        log::info!(
+            "配方 {}: {}",
+            說明.配方,
+            說明.description.as_deref().unwrap_or("")
+        );
+    }
+    if let Some(配方文件) = &配方文件 {
+        for 補丁文件 in 配方文件.補丁文件.keys() {
+            核對相對路徑(Path::new(補丁文件))?;
+            if !補丁文件.ends_with(".custom.yaml") {
+                bail!("補丁文件應命名爲 <id>.custom.yaml: {補丁文件}");
+            }
+        }
+    }
+    let 衆文件 = 列出配方的安裝文件(&本地倉庫, 配方文件.as_ref())?;
+    let 只打補丁 = matches!(&配方文件, Some(配方文件) if 配方文件.安裝文件.is_none());
+    if 衆文件.is_empty() && !只打補丁 {
+        log::warn!("配方包裏沒有可安裝的文件: {配方}");
+    }
+    for 文件 in &衆文件 {
+        let 目標文件 = 工作場地.join(文件);
+        if let Some(目錄) = 目標文件.parent() {
+            fs::create_dir_all(目錄)?;
+        }
+        fs::copy(本地倉庫.join(文件), &目標文件)?;
+        log::debug!("安裝文件: {}", 文件.display());
+    }
+    log::info!("安裝好了 {} 個文件. {配方}", 衆文件.len());
+    let 記錄路徑 = 工作場地.join(安裝記錄文件名);
+    let mut 記錄 = 安裝記錄::讀取或新建(&記錄路徑)?;
+    記錄.記錄(配方, 工作場地, &衆文件)?;
+    記錄.寫入(&記錄路徑)?;
+
+    if let Some(配方文件) = &配方文件 {
+        應用補丁(配方, 配方文件, 工作場地)?;
+    }
+    Ok(())
+}
+
+/// 配方包中描述安裝步驟的文件.
+fn 配方文件名(配方: &配方名片) -> String {
+    match &配方.配方名 {
+        Some(配方名) => format!("{配方名}.recipe.yaml"),
+        None => "recipe.yaml".to_owned(),
+    }
+}
+
+/// 讀取已下載的配方包中的配方文件. 未指定配方名時, 配方文件可有可無.
+pub fn 讀取配方文件(
+    配方: &配方名片,
+    工作場地: &Path,
+) -> anyhow::Result<Option<配方文件>> {
+    let 包 = 配方包 {
+        配方: 配方.clone(),
+        倉庫域名: None,
+    };
+    let 配方文件路徑 = 包.本地路徑(工作場地).join(配方文件名(配方));
+    if 配方文件路徑.exists() {
+        Ok(Some(配方文件::讀取(&配方文件路徑, &配方.配方參數)?))
+    } else if 配方.配方名.is_some() {
+        bail!("配方包中沒有配方文件: {}", 配方文件路徑.display());
+    } else {
+        Ok(None)
+    }
+}
+
+/// 同 plum, 把補丁寫進工作場地中 `<id>.custom.yaml` 的 `__patch` 列表, 由引擎加載時原樣應用,
+/// 如 `patch/+` 合併進 `patch`. 前後以 `# Rx: <配方> {` 和 `# }` 標記, 重裝時替換這一段.
+fn 應用補丁(
+    配方: &配方名片, 配方文件: &配方文件, 工作場地: &Path
+) -> anyhow::Result<()> {
+    let 配方 = 配方.不論版本().to_string();
+    for (文件, 補丁) in &配方文件.補丁文件 {
+        let 路徑 = 工作場地.join(文件);
+        let 原文 = if 路徑.exists() {
+            fs::read_to_string(&路徑)?
+        } else {
+            String::new()
+        };
+        if let Some(目錄) = 路徑.parent() {
+            fs::create_dir_all(目錄)?;
+        }
+        fs::write(&路徑, 寫入補丁(&原文, &配方, 補丁)?)?;
+        log::info!("補丁打好了. {文件}");
+    }
+    Ok(())
+}
+
+/// 在 custom.yaml 的內容 `原文` 中寫入 `配方` 的補丁, 返回新的內容.
+fn 寫入補丁(原文: &str, 配方: &str, 補丁: &補丁內容) -> anyhow::Result<String> {
+    let 衆字典 = 補丁.衆字典();
+    let 開始標記 = format!("# Rx: {配方} {{");
+    let mut 段落 = vec![開始標記.clone()];
+    if !衆字典.is_empty() {
+        for 行 in serde_yaml::to_string(衆字典)?.lines() {
+            段落.push(format!("  {行}"));
+        }
+    }
+    段落.push("# }".to_owned());
+
+    let 衆行 = 原文.lines().collect::<Vec<_>>();
+    let 拼接 = |前: &[&str], 中: &[String], 後: &[&str]| {
+        let mut 結果 = String::new();
+        for 行 in 前
+            .iter()
+            .copied()
+            .chain(中.iter().map(String::as_str))
+            .chain(後.iter().copied())
+        {
+            結果.push_str(行);
+            結果.push('\n');
+        }
+        結果
+    };
+    // 重裝的配方, 換掉先前寫入的一段
+    if let Some(開始) = 衆行.iter().position(|行| *行 == 開始標記) {
+        let 結束 = 衆行[開始..]
+            .iter()
+            .position(|行| *行 == "# }")
+            .ok_or(anyhow!("補丁的結束標記 `# }}` 不見了: {開始標記}"))?;
+        return Ok(拼接(&衆行[..開始], &段落, &衆行[開始 + 結束 + 1..]));
+    }
+    match 衆行.iter().position(|行| 行.starts_with("__patch:")) {
+        Some(位置) if 衆行[位置].trim_end() != "__patch:" => {
+            bail!("__patch 應寫成分行的列表, 纔能加入配方 {配方} 的補丁")
+        }
+        // 加到 `__patch` 列表的末尾, 即下一個頂層的紐之前
+        Some(位置) => {
+            let 結束 = 衆行[位置 + 1..]
+                .iter()
+                .position(|行| !行.is_empty() && !行.starts_with([' ', '-', '#']))
+                .map_or(衆行.len(), |下一個| 位置 + 1 + 下一個);
+            Ok(拼接(&衆行[..結束], &段落, &衆行[結束..]))
+        }
+        None => {
+            段落.insert(0, "__patch:".to_owned());
+            Ok(拼接(&衆行, &段落, &[]))
+        }
+    }
+}
+
+/// 配方要安裝的文件. 同 plum, 有配方文件的只安裝其中列出的; 沒有配方文件的, 按慣例安裝.
+fn 列出配方的安裝文件(
+    本地倉庫: &Path,
+    配方文件: Option<&配方文件>,
+) -> anyhow::Result<Vec<PathBuf>> {
+    match 配方文件.map(|配方文件| 配方文件.安裝文件.as_ref()) {
+        // 配方明確列出的文件, 一律安裝
+        Some(Some(安裝文件)) => 列出安裝文件(本地倉庫, &安裝文件.衆模式(), &[]),
+        // 只打補丁的配方, 如 emoji:customize
+        Some(None) => Ok(vec![]),
+        None => 列出安裝文件(本地倉庫, 默認安裝文件, 排除文件),
+    }
+}
+
+/// 按模式列出倉庫中要安裝的文件, 返回相對於倉庫的路徑.
+/// 模式來自配方, 不可信: 不得列出倉庫以外的文件.
+fn 列出安裝文件(
+    本地倉庫: &Path,
+    衆模式: &[&str],
+    衆排除模式: &[&str],
+) -> anyhow::Result<Vec<PathBuf>> {
+    for 模式 in 衆模式 {
+        核對相對路徑(Path::new(模式))?;
+    }
+    let 衆文件 = 列出文件(本地倉庫, 衆模式, 衆排除模式)?;
+    for 文件 in &衆文件 {
+        核對相對路徑(文件)?;
+    }
+    Ok(衆文件)
+}
+
+/// 配方中的路徑須在配方包以內: 不能是絕對路徑, 也不能含 `..`.
+fn 核對相對路徑(路徑: &Path) -> anyhow::Result<()> {
+    if !路徑
+        .components()
+        .all(|部分| matches!(部分, Component::Normal(_) | Component::CurDir))
+    {
+        bail!("配方中的路徑不能指向配方包以外: {}", 路徑.display());
+    }
+    Ok(())
+}
+
+/// 按模式列出目錄中的文件, 跳過文件名符合排除模式的. 返回相對於該目錄的路徑.
+pub fn 列出文件(
+    目錄: &Path,
+    衆模式: &[&str],
+    衆排除模式: &[&str],
+) -> anyhow::Result<Vec<PathBuf>> {
+    let 目錄路徑 = 目錄.to_str().ok_or(anyhow!("路徑編碼轉換錯誤"))?;
+    let 排除模式 = 衆排除模式
+        .iter()
+        .map(|模式| Pattern::new(模式))
+        .collect::<Result<Vec<_>, _>>()?;
+    // glob 返回的路徑省去了 `./`, 要從去掉 `./` 的目錄算起
+    let 去掉當前目錄 = 目錄
+        .components()
+        .filter(|部分| 部分 != &Component::CurDir)
+        .collect::<PathBuf>();
+    let mut 衆文件 = vec![];
+    for 模式 in 衆模式 {
+        let 完整模式 = format!("{}/{}", Pattern::escape(目錄路徑), 模式);
+        for 路徑 in glob::glob(&完整模式)? {
+            let 路徑 = 路徑?;
+            if !路徑.is_file() {
+                continue;
+            }
+            let 文件名 = 路徑.file_name().and_then(|名| 名.to_str()).unwrap_or("");
+            if 排除模式.iter().any(|排除| 排除.matches(文件名)) {
+                continue;
+            }
+            衆文件.push(路徑.strip_prefix(&去掉當前目錄)?.to_path_buf());
+        }
+    }
+    衆文件.sort();
+    衆文件.dedup();
+    Ok(衆文件)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use claims::assert_ok;
+    use std::collections::BTreeMap;
+    use std::fs::write;
+    use std::str::FromStr;
+
+    #[test]
+    fn 測試列出安裝文件() {
+        let tmp_dir = assert_ok!(tempfile::tempdir());
+        let 倉庫 = tmp_dir.path();
+        assert_ok!(fs::create_dir_all(倉庫.join("opencc")));
+        for 文件 in [
+            "ohmyrime.schema.yaml",
+            "ohmyrime.dict.yaml",
+            "ohmyrime.custom.yaml",
+            "ohmyrime.recipe.yaml",
+            "recipe.yaml",
+            "symbols.txt",
+            "README.md",
+            "opencc/emoji.json",
+            "opencc/emoji.txt",
+        ] {
+            assert_ok!(write(倉庫.join(文件), ""));
+        }
+
+        let 衆文件 = assert_ok!(列出安裝文件(倉庫, 默認安裝文件, 排除文件));
+        assert_eq!(
+            衆文件,
+            [
+                "ohmyrime.dict.yaml",
+                "ohmyrime.schema.yaml",
+                "opencc/emoji.json",
+                "opencc/emoji.txt",
+                "symbols.txt",
+            ]
+            .iter()
+            .map(PathBuf::from)
+            .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn 測試列出配方指定的安裝文件() {
+        let tmp_dir = assert_ok!(tempfile::tempdir());
+        let 倉庫 = tmp_dir.path().join("pkg");
+        assert_ok!(fs::create_dir_all(&倉庫));
+        assert_ok!(write(倉庫.join("ohmyrime.custom.yaml"), ""));
+        assert_ok!(write(tmp_dir.path().join("secret.yaml"), ""));
+
+        // 配方明確列出的, 不按默認的排除
+        assert_eq!(
+            assert_ok!(列出安裝文件(&倉庫, &["*.custom.yaml"], &[])),
+            [PathBuf::from("ohmyrime.custom.yaml")]
+        );
+        assert!(列出安裝文件(&倉庫, &["../*.yaml"], &[]).is_err());
+        assert!(列出安裝文件(&倉庫, &["**/../*.yaml"], &[]).is_err());
+        let 絕對路徑 = format!("{}/*.yaml", tmp_dir.path().display());
+        assert!(列出安裝文件(&倉庫, &[&絕對路徑], &[]).is_err());
+        assert!(核對相對路徑(Path::new("../default.custom.yaml")).is_err());
+        assert_ok!(核對相對路徑(Path::new("default.custom.yaml")));
+    }
+
+    #[test]
+    fn 測試只打補丁的配方不安裝文件() {
+        let tmp_dir = assert_ok!(tempfile::tempdir());
+        let 倉庫 = tmp_dir.path();
+        assert_ok!(write(倉庫.join("emoji.schema.yaml"), ""));
+        assert_ok!(write(倉庫.join("emoji.txt"), ""));
+        let 配方 = assert_ok!(配方文件::解析(
+            r#"
+recipe:
+  Rx: emoji/customize
+patch_files:
+  luna_pinyin.custom.yaml:
+    patch/switches/@next:
+      name: emoji_suggestion
+"#
+        ));
+
+        assert!(assert_ok!(列出配方的安裝文件(倉庫, Some(&配方))).is_empty());
+        // 沒有配方文件的, 才按慣例安裝
+        assert_eq!(
+            assert_ok!(列出配方的安裝文件(倉庫, None)),
+            [
+                PathBuf::from("emoji.schema.yaml"),
+                PathBuf::from("emoji.txt")
+            ]
+        );
+    }
+
+    #[test]
+    fn 測試在相對路徑的工作場地安裝() {
+        // 如缺省的 `--workspace .`, 路徑以 `./` 開頭.
+        // 從當前目錄經 `..` 繞到臨時目錄, 不在包目錄裏留下文件
+        let tmp_dir = assert_ok!(tempfile::tempdir());
+        let 是目錄名 = |部分: &Component| matches!(部分, Component::Normal(_));
+        let 當前目錄 = assert_ok!(std::env::current_dir());
+        let 工作場地 = 當前目錄
+            .components()
+            .filter(是目錄名)
+            .fold(PathBuf::from("."), |路徑, _| 路徑.join(".."))
+            .join(
+                tmp_dir
+                    .path()
+                    .components()
+                    .filter(是目錄名)
+                    .collect::<PathBuf>(),
+            );
+        assert!(工作場地.is_relative());
+        let 配方 = assert_ok!(配方名片::from_str("lotem/rime-ohmyrime"));
+        let 本地倉庫 = 工作場地.join("pkg/lotem/rime-ohmyrime");
+        assert_ok!(fs::create_dir_all(&本地倉庫));
+        assert_ok!(write(本地倉庫.join("ohmyrime.schema.yaml"), "schema:"));
+
+        assert_ok!(安裝配方(&配方, &引擎目錄::工作場地(&工作場地)));
+        assert!(工作場地.join("ohmyrime.schema.yaml").is_file());
+    }
+
+    /// rime-emoji 的 customize.recipe.yaml
+    const 繪文字配方: &str = r#"
+recipe:
+  Rx: emoji:customize
+  args:
+    - schema=luna_pinyin
+  description: >-
+    Enable emoji_suggestion for a schema
+patch_files:
+  ${schema}.custom.yaml:
+    patch/+:
+      __include: emoji_suggestion:/patch
+"#;
+
+    #[test]
+    fn 測試應用補丁() {
+        let tmp_dir = assert_ok!(tempfile::tempdir());
+        let 工作場地 = tmp_dir.path();
+        let 配方 = assert_ok!(配方名片::from_str("emoji:customize:schema=bopomofo"));
+        let 參數 = BTreeMap::from([("schema".to_owned(), "bopomofo".to_owned())]);
+        let 配方文件 = assert_ok!(配方文件::代入參數解析(繪文字配方, &參數));
+        assert_ok!(write(
+            工作場地.join("bopomofo.custom.yaml"),
+            "patch:\n  menu/page_size: 9\n"
+        ));
+
+        // 重裝不重複寫入
+        assert_ok!(應用補丁(&配方, &配方文件, 工作場地));
+        assert_ok!(應用補丁(&配方, &配方文件, 工作場地));
+        let 內容 = assert_ok!(fs::read_to_string(工作場地.join("bopomofo.custom.yaml")));
+        assert_eq!(內容.matches("# Rx: ").count(), 1);
+        let 結果: serde_yaml::Value = assert_ok!(serde_yaml::from_str(&內容));
+        assert_eq!(結果["patch"]["menu/page_size"], serde_yaml::Value::from(9));
+        assert_eq!(
+            結果["__patch"][0]["patch/+"]["__include"],
+            serde_yaml::Value::from("emoji_suggestion:/patch")
+        );
+    }
+
+    #[test]
+    fn 測試寫入補丁() {
+        let 補丁 = |內容: &str| -> 補丁內容 { serde_yaml::from_str(內容).unwrap() };
+        let 原文 = "__patch:\n  - foo:/patch\npatch:\n  menu/page_size: 9\n";
+        let 結果 = assert_ok!(寫入補丁(原文, "甲", &補丁("__include: bar:/")));
+        assert_eq!(
+            結果,
+            "__patch:\n  - foo:/patch\n# Rx: 甲 {\n  - __include: bar:/\n# }\npatch:\n  menu/page_size: 9\n"
+        );
+        // 替換同一配方先前寫入的
+        let 結果 = assert_ok!(寫入補丁(&結果, "甲", &補丁("- __include: baz:/")));
+        assert!(結果.contains("# Rx: 甲 {\n  - __include: baz:/\n# }\n"));
+        assert!(!結果.contains("bar:/"));
+        assert!(寫入補丁("__patch: [foo:/patch]\n", "甲", &補丁("a: 1")).is_err());
+    }
 }
@@ -0,0 +1,147 @@
+use crate::recipe::配方名片;
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// 記錄各配方安裝了哪些文件, 以便卸載.
+pub const 安裝記錄文件名: &str = "rime-recipes.installed";
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct 安裝記錄 {
+    /// 以不論版本的配方名片爲紐
+    #[serde(rename = "recipes", default)]
+    pub 衆配方: BTreeMap<String, 配方安裝記錄>,
+}
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct 配方安裝記錄 {
+    /// 文件相對於工作場地的路徑, 及安裝時文件內容的摘要
+    #[serde(rename = "files", default)]
+    pub 衆文件: BTreeMap<String, String>,
+}
+
+impl 安裝記錄 {
+    pub fn 讀取(路徑: &Path) -> anyhow::Result<Self> {
+        let 內容 = std::fs::read_to_string(路徑)
+            .map_err(|e| anyhow!("讀取安裝記錄 {} 失敗: {}", 路徑.display(), e))?;
+        Ok(serde_yaml::from_str(&內容)?)
+    }
+
+    pub fn 讀取或新建(路徑: &Path) -> anyhow::Result<Self> {
+        if 路徑.exists() {
+            Self::讀取(路徑)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    pub fn 寫入(&self, 路徑: &Path) -> anyhow::Result<()> {
+        let 內容 = serde_yaml::to_string(self)?;
+        std::fs::write(路徑, format!("# 由 rime 生成, 請勿手工修改\n{內容}"))?;
+        Ok(())
+    }
+
+    /// 記下配方安裝到工作場地的文件. 重新安裝時替換舊的記錄.
+    pub fn 記錄(
+        &mut self,
+        配方: &配方名片,
+        工作場地: &Path,
+        衆文件: &[PathBuf],
+    ) -> anyhow::Result<()> {
+        let mut 配方記錄 = 配方安裝記錄::default();
+        for 文件 in 衆文件 {
+            let 文件名 = 文件.to_str().ok_or(anyhow!("路徑編碼轉換錯誤"))?;
+            配方記錄
+                .衆文件
+                .insert(文件名.to_owned(), 文件摘要(&工作場地.join(文件))?);
+        }
+        self.衆配方.insert(配方.不論版本().to_string(), 配方記錄);
+        Ok(())
+    }
+
+    pub fn 移除(&mut self, 配方: &配方名片) -> Option<配方安裝記錄> {
+        self.衆配方.remove(&配方.不論版本().to_string())
+    }
+
+    /// 文件是否也是別的配方安裝的.
+    pub fn 有配方用到(&self, 文件: &str) -> bool {
+        self.衆配方
+            .values()
+            .any(|配方記錄| 配方記錄.衆文件.contains_key(文件))
+    }
+
+    /// 還有同一配方包中的配方安裝着.
+    pub fn 有配方來自(&self, 配方: &配方名片) -> anyhow::Result<bool> {
+        for 已安裝 in self.衆配方.keys() {
+            if 已安裝.parse::<配方名片>()?.倉庫名片() == 配方.倉庫名片() {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+}
+
+/// 文件內容的摘要, 用來判斷安裝後用戶是否改動過.
+pub fn 文件摘要(路徑: &Path) -> anyhow::Result<String> {
+    let 內容 =
+        std::fs::read(路徑).map_err(|e| anyhow!("讀取文件 {} 失敗: {}", 路徑.display(), e))?;
+    Ok(git2::Oid::hash_object(git2::ObjectType::Blob, &內容)?.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::配方;
+    use claims::assert_ok;
+
+    #[test]
+    fn 測試安裝記錄() {
+        let tmp_dir = assert_ok!(tempfile::tempdir());
+        let 工作場地 = tmp_dir.path();
+        assert_ok!(std::fs::write(工作場地.join("foo.schema.yaml"), "foo"));
+        assert_ok!(std::fs::write(工作場地.join("foo.dict.yaml"), ""));
+
+        let mut 記錄 = 安裝記錄::default();
+        assert_ok!(記錄.記錄(
+            &配方("foo@v1"),
+            工作場地,
+            &[PathBuf::from("foo.schema.yaml")]
+        ));
+        assert_ok!(記錄.記錄(
+            &配方("foo"),
+            工作場地,
+            &[
+                PathBuf::from("foo.dict.yaml"),
+                PathBuf::from("foo.schema.yaml")
+            ]
+        ));
+        assert_ok!(記錄.記錄(
+            &配方("foo:extra"),
+            工作場地,
+            &[PathBuf::from("foo.dict.yaml")]
+        ));
+        let 內容 = assert_ok!(serde_yaml::to_string(&記錄));
+        assert_eq!(
+            內容,
+            r#"recipes:
+  rime/rime-foo:
+    files:
+      foo.dict.yaml: e69de29bb2d1d6434b8b29ae775ad8c2e48c5391
+      foo.schema.yaml: 19102815663d23f8b75a47e7a01965dcdc96468c
+  rime/rime-foo:extra:
+    files:
+      foo.dict.yaml: e69de29bb2d1d6434b8b29ae775ad8c2e48c5391
+"#
+        );
+        assert_eq!(assert_ok!(serde_yaml::from_str::<安裝記錄>(&內容)), 記錄);
+
+        assert!(記錄.移除(&配方("foo@v2")).is_some());
+        assert!(記錄.有配方用到("foo.dict.yaml"));
+        assert!(!記錄.有配方用到("foo.schema.yaml"));
+        assert!(assert_ok!(記錄.有配方來自(&配方("foo"))));
+        assert!(!assert_ok!(記錄.有配方來自(&配方("bar"))));
+    }
+}
@@ -0,0 +1,220 @@
+use crate::download::git;
+use crate::installed::{安裝記錄, 安裝記錄文件名};
+use crate::package::配方包;
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Serialize)]
+pub struct 工作場地概況 {
+    #[serde(rename = "packages")]
+    pub 衆配方包: Vec<配方包概況>,
+    #[serde(rename = "recipes")]
+    pub 衆配方: Vec<配方概況>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct 配方包概況 {
+    #[serde(rename = "name")]
+    pub 名字: String,
+    #[serde(rename = "path")]
+    pub 路徑: PathBuf,
+    #[serde(rename = "branch")]
+    pub 分支: Option<String>,
+    #[serde(rename = "commit")]
+    pub 提交: Option<String>,
+    /// 有未提交的改動
+    #[serde(rename = "dirty")]
+    pub 有改動: bool,
+    /// 領先上游的提交數, 以上次取回的上游爲準
+    #[serde(rename = "ahead")]
+    pub 領先: Option<usize>,
+    /// 落後上游的提交數, 以上次取回的上游爲準
+    #[serde(rename = "behind")]
+    pub 落後: Option<usize>,
+    /// 讀不了倉庫時的錯誤
+    #[serde(rename = "error", skip_serializing_if = "Option::is_none")]
+    pub 錯誤: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct 配方概況 {
+    #[serde(rename = "name")]
+    pub 名字: String,
+    #[serde(rename = "files")]
+    pub 文件數: usize,
+}
+
+/// 列出工作場地中下載的配方包和安裝了的配方.
+pub fn 列出工作場地(工作場地: &Path, json: bool) -> anyhow::Result<()> {
+    let 概況 = 工作場地概況 {
+        衆配方包: 列出配方包(工作場地)?,
+        衆配方: 列出已安裝配方(工作場地)?,
+    };
+    if json {
+        println!("{}", serde_json::to_string_pretty(&概況)?);
+    } else {
+        print!("{}", 列表(&概況));
+    }
+    Ok(())
+}
+
+/// 查看 `pkg/<方家>/<名字>` 下的每個配方包.
+fn 列出配方包(工作場地: &Path) -> anyhow::Result<Vec<配方包概況>> {
+    Ok(配方包::列出下載的配方包(工作場地)?
+        .iter()
+        .map(|包| 查看配方包(包, 工作場地))
+        .collect())
+}
+
+fn 查看配方包(包: &配方包, 工作場地: &Path) -> 配方包概況 {
+    let 路徑 = 包.本地路徑(工作場地);
+    let 名字 = 包.配方.to_string();
+    match git::status(&路徑) {
+        Ok(狀態) => 配方包概況 {
+            名字,
+            路徑,
+            分支: 狀態.branch,
+            提交: Some(狀態.commit),
+            有改動: 狀態.dirty,
+            領先: 狀態.ahead_behind.map(|(領先, _)| 領先),
+            落後: 狀態.ahead_behind.map(|(_, 落後)| 落後),
+            錯誤: None,
+        },
+        Err(e) => 配方包概況 {
+            名字,
+            路徑,
+            錯誤: Some(e.message().to_owned()),
+            ..Default::default()
+        },
+    }
+}
+
+fn 列出已安裝配方(工作場地: &Path) -> anyhow::Result<Vec<配方概況>> {
+    let 記錄 = 安裝記錄::讀取或新建(&工作場地.join(安裝記錄文件名))?;
+    Ok(記錄
+        .衆配方
+        .iter()
+        .map(|(名字, 配方記錄)| 配方概況 {
+            名字: 名字.clone(),
+            文件數: 配方記錄.衆文件.len(),
+        })
+        .collect())
+}
+
+fn 列表(概況: &工作場地概況) -> String {
+    let mut 輸出 = String::new();
+    let 寬度 = 概況
+        .衆配方包
+        .iter()
+        .map(|包| 包.名字.len())
+        .chain(概況.衆配方.iter().map(|配方| 配方.名字.len()))
+        .max()
+        .unwrap_or(0);
+    輸出.push_str("配方包:\n");
+    if 概況.衆配方包.is_empty() {
+        輸出.push_str("  (無)\n");
+    }
+    for 包 in &概況.衆配方包 {
+        let 版本 = match (&包.分支, &包.提交) {
+            (Some(分支), Some(提交)) => format!("{分支}@{}", 短提交(提交)),
+            (None, Some(提交)) => 短提交(提交).to_owned(),
+            _ => "-".to_owned(),
+        };
+        let mut 狀態 = vec![];
+        if let Some(錯誤) = &包.錯誤 {
+            狀態.push(錯誤.clone());
+        }
+        if 包.有改動 {
+            狀態.push("有改動".to_owned());
+        }
+        if let Some(領先) = 包.領先.filter(|&n| n > 0) {
+            狀態.push(format!("領先 {領先}"));
+        }
+        if let Some(落後) = 包.落後.filter(|&n| n > 0) {
+            狀態.push(format!("落後 {落後}"));
+        }
+        輸出.push_str(format!("  {:寬度$}  {版本}  {}", 包.名字, 狀態.join(", ")).trim_end());
+        輸出.push('\n');
+    }
+    輸出.push_str("安裝了的配方:\n");
+    if 概況.衆配方.is_empty() {
+        輸出.push_str("  (無)\n");
+    }
+    for 配方 in &概況.衆配方 {
+        輸出.push_str(&format!("  {:寬度$}  {} 個文件\n", 配方.名字, 配方.文件數));
+    }
+    輸出
+}
+
+fn 短提交(提交: &str) -> &str {
+    &提交[..提交.len().min(7)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn 測試列表() {
+        let 概況 = 工作場地概況 {
+            衆配方包: vec![
+                配方包概況 {
+                    名字: "rime/rime-luna-pinyin".to_owned(),
+                    分支: Some("master".to_owned()),
+                    提交: Some("0123456789abcdef".to_owned()),
+                    有改動: true,
+                    領先: Some(0),
+                    落後: Some(2),
+                    ..Default::default()
+                },
+                配方包概況 {
+                    名字: "rime/rime-emoji".to_owned(),
+                    提交: Some("fedcba9876543210".to_owned()),
+                    ..Default::default()
+                },
+            ],
+            衆配方: vec![配方概況 {
+                名字: "rime/rime-luna-pinyin".to_owned(),
+                文件數: 3,
+            }],
+        };
+        assert_eq!(
+            列表(&概況),
+            r#"配方包:
+  rime/rime-luna-pinyin  master@0123456  有改動, 落後 2
+  rime/rime-emoji        fedcba9
+安裝了的配方:
+  rime/rime-luna-pinyin  3 個文件
+"#
+        );
+
+        let json = serde_json::to_value(&概況).unwrap();
+        assert_eq!(json["packages"][0]["behind"], 2);
+        assert_eq!(json["packages"][1]["branch"], serde_json::Value::Null);
+        assert_eq!(json["recipes"][0]["files"], 3);
+    }
+
+    #[test]
+    fn 測試列出配方包() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let 工作場地 = tmp_dir.path();
+        assert!(列出配方包(工作場地).unwrap().is_empty());
+
+        std::fs::create_dir_all(工作場地.join("pkg/rime/rime-broken")).unwrap();
+        git2::Repository::init(工作場地.join("pkg/lotem/rime-empty")).unwrap();
+        // 下載中的臨時目錄
+        std::fs::create_dir_all(工作場地.join("pkg/rime/.rime-foo.abc123")).unwrap();
+        let 衆配方包 = 列出配方包(工作場地).unwrap();
+        assert_eq!(
+            衆配方包
+                .iter()
+                .map(|包| 包.名字.as_str())
+                .collect::<Vec<_>>(),
+            ["lotem/rime-empty", "rime/rime-broken"]
+        );
+        // 空倉庫和非倉庫都讀不了 HEAD
+        assert!(衆配方包.iter().all(|包| 包.錯誤.is_some()));
+        assert_eq!(衆配方包[1].路徑, 工作場地.join("pkg/rime/rime-broken"));
+    }
+}
@@ -0,0 +1,215 @@
+use crate::package::配方包;
+use crate::recipe::配方名片;
+
+use anyhow::{anyhow, bail};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// 記錄各配方包下載到了哪個提交, 以便重現同樣的構建.
+pub const 鎖定文件名: &str = "rime-recipes.lock";
+
+#[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct 鎖定文件 {
+    /// 以倉庫名片爲紐
+    #[serde(rename = "packages", default)]
+    pub 衆配方包: BTreeMap<String, 鎖定記錄>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+pub struct 鎖定記錄 {
+    #[serde(rename = "url")]
+    pub 網址: String,
+    #[serde(rename = "commit")]
+    pub 提交: String,
+    #[serde(rename = "recipes", default)]
+    pub 衆配方: Vec<String>,
+}
+
+impl 鎖定文件 {
+    pub fn 讀取(路徑: &Path) -> anyhow::Result<Self> {
+        let 內容 = std::fs::read_to_string(路徑)
+            .map_err(|e| anyhow!("讀取鎖定文件 {} 失敗: {}", 路徑.display(), e))?;
+        Ok(serde_yaml::from_str(&內容)?)
+    }
+
+    /// 讀取既有的鎖定文件, 沒有則新建.
+    pub fn 讀取或新建(路徑: &Path) -> anyhow::Result<Self> {
+        if 路徑.exists() {
+            Self::讀取(路徑)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    pub fn 寫入(&self, 路徑: &Path) -> anyhow::Result<()> {
+        let 內容 = serde_yaml::to_string(self)?;
+        std::fs::write(路徑, format!("# 由 rime 生成, 請勿手工修改\n{內容}"))?;
+        Ok(())
+    }
+
+    pub fn 記錄(&mut self, 包: &配方包, 衆配方: &[配方包], 提交: String) {
+        let mut 衆配方 = 衆配方
+            .iter()
+            .map(|包| 包.配方.to_string())
+            .collect::<Vec<_>>();
+        衆配方.sort();
+        衆配方.dedup();
+        self.衆配方包.insert(
+            包.配方.倉庫名片().to_string(),
+            鎖定記錄 {
+                網址: 包.倉庫地址(),
+                提交,
+                衆配方,
+            },
+        );
+    }
+
+    /// 配方包更新後, 把下載到同一目錄的記錄改爲新的提交.
+    pub fn 更新提交(
+        &mut self,
+        本地倉庫: &Path,
+        工作場地: &Path,
+        提交: &str,
+    ) -> anyhow::Result<()> {
+        for 記錄 in self.衆配方包.values_mut() {
+            let Some(配方) = 記錄.衆配方.first() else {
+                continue;
+            };
+            let 包 = 配方包 {
+                配方: 配方.parse()?,
+                倉庫域名: None,
+            };
+            if 包.本地路徑(工作場地) == 本地倉庫 {
+                記錄.提交 = 提交.to_owned();
+            }
+        }
+        Ok(())
+    }
+
+    /// 把配方的版本換成鎖定的提交.
+    pub fn 鎖定版本(
+        &self,
+        衆配方: Vec<配方名片>,
+        倉庫域名: Option<&str>,
+    ) -> anyhow::Result<Vec<配方名片>> {
+        衆配方
+            .into_iter()
+            .map(|配方| {
+                let 包 = 配方包 {
+                    配方: 配方.clone(),
+                    倉庫域名,
+                };
+                if 包.是本地目錄() {
+                    return Ok(配方);
+                }
+                let 包名 = 配方.倉庫名片().to_string();
+                let 記錄 = self
+                    .衆配方包
+                    .get(&包名)
+                    .ok_or(anyhow!("鎖定文件中沒有配方包 {包名}"))?;
+                if 記錄.網址 != 包.倉庫地址() {
+                    bail!(
+                        "配方包 {} 的網址已變更: 鎖定的是 {}, 現在是 {}",
+                        包名,
+                        記錄.網址,
+                        包.倉庫地址()
+                    );
+                }
+                Ok(配方名片 {
+                    版本: Some(記錄.提交.clone()),
+                    ..配方
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::配方;
+
+    #[test]
+    fn 測試鎖定文件() {
+        let mut 鎖定 = 鎖定文件::default();
+        let 包 = |source| 配方包 {
+            配方: 配方(source),
+            倉庫域名: None,
+        };
+        鎖定.記錄(
+            &包("luna-pinyin"),
+            &[包("luna-pinyin@master"), 包("luna-pinyin")],
+            "0123456789abcdef0123456789abcdef01234567".to_owned(),
+        );
+        let 內容 = serde_yaml::to_string(&鎖定).unwrap();
+        assert_eq!(
+            內容,
+            r#"packages:
+  rime/rime-luna-pinyin:
+    url: https://github.com/rime/rime-luna-pinyin.git
+    commit: 0123456789abcdef0123456789abcdef01234567
+    recipes:
+    - rime/rime-luna-pinyin
+    - rime/rime-luna-pinyin@master
+"#
+        );
+        assert_eq!(serde_yaml::from_str::<鎖定文件>(&內容).unwrap(), 鎖定);
+    }
+
+    #[test]
+    fn 測試鎖定版本() {
+        let mut 鎖定 = 鎖定文件::default();
+        let 包 = 配方包 {
+            配方: 配方("emoji"),
+            倉庫域名: None,
+        };
+        鎖定.記錄(&包, std::slice::from_ref(&包), "abcdef0".to_owned());
+
+        let 衆配方 = 鎖定
+            .鎖定版本(
+                vec![配方("emoji@master:customize:schema=luna_pinyin")],
+                None,
+            )
+            .unwrap();
+        assert_eq!(
+            衆配方[0].to_string(),
+            "rime/rime-emoji@abcdef0:customize:schema=luna_pinyin"
+        );
+
+        // 本地目錄不鎖定
+        let 衆配方 = 鎖定.鎖定版本(vec![配方("./my-recipe")], None).unwrap();
+        assert_eq!(衆配方[0].版本, None);
+
+        assert!(鎖定.鎖定版本(vec![配方("bopomofo")], None).is_err());
+        assert!(鎖定
+            .鎖定版本(vec![配方("emoji")], Some("gitee.com"))
+            .is_err());
+    }
+
+    #[test]
+    fn 測試更新提交() {
+        let mut 鎖定 = 鎖定文件::default();
+        for source in ["emoji", "luna-pinyin", "file:///src/rime-foo.git"] {
+            let 包 = 配方包 {
+                配方: 配方(source),
+                倉庫域名: None,
+            };
+            鎖定.記錄(&包, std::slice::from_ref(&包), "abcdef0".to_owned());
+        }
+        let 工作場地 = Path::new("/ws");
+        鎖定
+            .更新提交(Path::new("/ws/pkg/rime/rime-emoji"), 工作場地, "1234567")
+            .unwrap();
+        鎖定
+            .更新提交(Path::new("/ws/pkg/src/rime-foo"), 工作場地, "7654321")
+            .unwrap();
+        let 衆提交 = 鎖定
+            .衆配方包
+            .values()
+            .map(|記錄| 記錄.提交.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(衆提交, ["7654321", "1234567", "abcdef0"]);
+    }
+}
@@ -1,21 +1,65 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 
+mod dependency;
+mod deploy;
 mod download;
 mod install;
+mod installed;
+mod list;
+mod lock;
+mod manifest;
 mod package;
 mod recipe;
 mod rime_levers;
+mod scaffold;
+#[cfg(test)]
+mod testing;
+mod uninstall;
+mod update;
 
-use download::{下載參數, 下載配方包};
+use dependency::下載配方及依賴;
+use deploy::{部署參數, 部署輸入法固件};
+use download::{下載參數, 更新鏡像};
 use install::安裝配方;
+use list::列出工作場地;
+use manifest::{移出配方清單, 缺省取清單中的配方, 記入配方清單};
 use recipe::配方名片;
 use rime_levers::{
-    加入輸入方案列表, 製備輸入法固件, 設置引擎啓動參數, 選擇輸入方案, 配置補丁
+    加入輸入方案列表, 引擎目錄, 製備輸入法固件, 設置引擎啓動參數, 選擇輸入方案, 配置補丁,
 };
+use scaffold::{新建參數, 新建配方};
+use uninstall::卸載配方;
+use update::{更新參數, 更新配方包};
 
 #[derive(Debug, StructOpt)]
 #[structopt(about = "Rime 配方管理器")]
+struct 命令行參數 {
+    /// 工作場地, 即 Rime 用戶數據目錄
+    #[structopt(
+        short,
+        long,
+        alias = "user-data-dir",
+        env = "RIME_USER_DATA_DIR",
+        default_value = ".",
+        global = true,
+        parse(from_os_str)
+    )]
+    workspace: PathBuf,
+    /// 共享數據目錄, 缺省同工作場地
+    #[structopt(long, global = true, parse(from_os_str))]
+    shared_data_dir: Option<PathBuf>,
+    /// 預構建數據目錄, 缺省爲共享數據目錄下的 build
+    #[structopt(long, global = true, parse(from_os_str))]
+    prebuilt_data_dir: Option<PathBuf>,
+    /// 整備區, 即構建輸出目錄, 缺省爲工作場地下的 build
+    #[structopt(long, global = true, parse(from_os_str))]
+    staging_dir: Option<PathBuf>,
+    #[structopt(subcommand)]
+    子命令: 子命令,
+}
+
+#[derive(Debug, StructOpt)]
 enum 子命令 {
     /// 加入輸入方案列表
     Add {
@@ -25,25 +69,47 @@ enum 子命令 {
     /// 構建輸入法固件
     Build,
     /// 部署輸入法固件到目標位置
-    Deploy,
+    Deploy {
+        #[structopt(flatten)]
+        部署參數: 部署參數,
+    },
     /// 下載配方包
     Download {
-        /// 要下載的配方包
-        recipes: Vec<String>,
+        /// 要下載的配方包, 缺省爲配方清單 rime-recipes.yaml 中的全部配方
+        recipes: Vec<配方名片>,
         #[structopt(flatten)]
         下載參數: 下載參數,
     },
     /// 安裝配方
     Install {
-        /// 要安裝的配方
-        recipes: Vec<String>,
+        /// 要安裝的配方, 缺省爲配方清單 rime-recipes.yaml 中的全部配方
+        recipes: Vec<配方名片>,
+        /// 把配方記入配方清單 rime-recipes.yaml. 增改清單中的配方請用此選項:
+        /// 新配方添在末尾, 已有的配方只改版本, 清單中的註釋和格式照舊
+        #[structopt(long)]
+        save: bool,
+        #[structopt(flatten)]
+        下載參數: 下載參數,
+    },
+    /// 列出下載的配方包和安裝了的配方
+    List {
+        /// 以 JSON 格式輸出
+        #[structopt(long)]
+        json: bool,
+    },
+    /// 下載配方及其依賴, 並把配方包鏡像到 --mirror 目錄, 供 --offline 時使用
+    Mirror {
+        /// 要鏡像的配方, 缺省爲配方清單 rime-recipes.yaml 中的全部配方
+        recipes: Vec<配方名片>,
         #[structopt(flatten)]
         下載參數: 下載參數,
     },
     /// 新建配方
     New {
-        /// 配方名字
-        _name: Option<String>,
+        /// 配方名字, 如 my-schema. 配方建在當前目錄下的 rime-my-schema
+        name: String,
+        #[structopt(flatten)]
+        新建參數: 新建參數,
     },
     /// 配置補丁
     Patch {
@@ -54,60 +120,105 @@ enum 子命令 {
         /// 值
         value: String,
     },
+    /// 卸載配方, 並把它移出配方清單
+    #[structopt(alias = "uninstall")]
+    Remove {
+        /// 要卸載的配方
+        #[structopt(required = true)]
+        recipes: Vec<配方名片>,
+        /// 同時刪除下載的配方包
+        #[structopt(long)]
+        purge: bool,
+    },
     /// 選擇輸入方案
     Select {
         /// 選中的輸入方案
         schema: String,
     },
+    /// 更新下載了的全部配方包
+    Update {
+        #[structopt(flatten)]
+        更新參數: 更新參數,
+    },
 }
 
 fn main() -> anyhow::Result<()> {
     env_logger::init();
 
-    let 命令行參數 = 子命令::from_args();
+    let 命令行參數 = 命令行參數::from_args();
     log::debug!("參數: {:?}", 命令行參數);
+    let 工作場地 = 命令行參數.workspace;
+    let 預設目錄 = 引擎目錄::工作場地(&工作場地);
+    let 引擎目錄 = 引擎目錄 {
+        共享數據: 命令行參數.shared_data_dir.unwrap_or(預設目錄.共享數據),
+        預構建數據: 命令行參數.prebuilt_data_dir,
+        整備區: 命令行參數.staging_dir,
+        ..預設目錄
+    };
 
-    match 命令行參數 {
+    match 命令行參數.子命令 {
         子命令::Add { schemata } => {
-            let 還不知道怎麼傳過來 = PathBuf::from(".");
-            設置引擎啓動參數(&還不知道怎麼傳過來)?;
+            設置引擎啓動參數(&引擎目錄)?;
             加入輸入方案列表(&schemata)?;
         }
         子命令::Build => {
-            let 還不知道怎麼傳過來 = PathBuf::from(".");
-            設置引擎啓動參數(&還不知道怎麼傳過來)?;
+            設置引擎啓動參數(&引擎目錄)?;
             製備輸入法固件()?;
         }
+        子命令::Deploy { 部署參數 } => {
+            部署輸入法固件(&引擎目錄, &部署參數)?;
+        }
         子命令::Download {
             recipes, 下載參數
         } => {
-            let 衆配方 = recipes
-                .iter()
-                .map(|rx| 配方名片::from(rx.as_str()))
-                .collect::<Vec<_>>();
-            下載配方包(&衆配方, 下載參數)?;
+            let 衆配方 = 缺省取清單中的配方(&recipes, &工作場地)?;
+            下載配方及依賴(&衆配方, &下載參數, &工作場地)?;
         }
         子命令::Install {
-            recipes, 下載參數
+            recipes,
+            save,
+            下載參數,
         } => {
-            let 衆配方 = recipes
-                .iter()
-                .map(|rx| 配方名片::from(rx.as_str()))
-                .collect::<Vec<_>>();
-            下載配方包(&衆配方, 下載參數)?;
-            for 配方 in &衆配方 {
-                安裝配方(配方)?;
+            let 衆配方 = 缺省取清單中的配方(&recipes, &工作場地)?;
+            let 下載參數 = 下載參數.默認深度(1);
+            let 安裝順序 = 下載配方及依賴(&衆配方, &下載參數, &工作場地)?;
+            for 配方 in &安裝順序 {
+                安裝配方(配方, &引擎目錄)?;
             }
+            if save {
+                記入配方清單(&recipes, &工作場地)?;
+            }
+        }
+        子命令::List { json } => {
+            列出工作場地(&工作場地, json)?;
+        }
+        子命令::Mirror {
+            recipes, 下載參數
+        } => {
+            let 衆配方 = 缺省取清單中的配方(&recipes, &工作場地)?;
+            let 衆配方 = 下載配方及依賴(&衆配方, &下載參數, &工作場地)?;
+            更新鏡像(&衆配方, &下載參數)?;
+        }
+        子命令::New { name, 新建參數 } => {
+            新建配方(&name, &新建參數, Path::new("."))?;
         }
         子命令::Patch { config, key, value } => {
-            let 還不知道怎麼傳過來 = PathBuf::from(".");
-            設置引擎啓動參數(&還不知道怎麼傳過來)?;
+            設置引擎啓動參數(&引擎目錄)?;
             配置補丁(&config, &key, &value)?;
         }
+        子命令::Remove { recipes, purge } => {
+            for 配方 in &recipes {
+                卸載配方(配方, &引擎目錄, purge)?;
+            }
+            移出配方清單(&recipes, &工作場地)?;
+        }
         子命令::Select { schema } => {
+            設置引擎啓動參數(&引擎目錄)?;
             選擇輸入方案(&schema)?;
         }
-        _ => todo!("還沒做呢"),
+        子命令::Update { 更新參數 } => {
+            更新配方包(&引擎目錄, &更新參數)?;
+        }
     }
 
     Ok(())
@@ -0,0 +1,480 @@
+use crate::recipe::{改寫版本, 配方名片};
+
+use anyhow::{anyhow, bail};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::path::Path;
+use std::str::FromStr;
+
+/// 工作場地所需的配方, 省得每次在命令行上一一列出.
+pub const 清單文件名: &str = "rime-recipes.yaml";
+
+/// 改動清單時只改相關的行, 保留用戶寫的註釋和格式.
+#[derive(Debug, Default, Deserialize)]
+pub struct 配方清單 {
+    #[serde(rename = "recipes", default)]
+    pub 衆條目: Vec<清單條目>,
+    #[serde(skip)]
+    原文: String,
+}
+
+/// 可以像命令行參數那樣寫成一串, 也可以分項寫.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum 清單條目 {
+    簡寫(String),
+    詳寫 {
+        name: String,
+        #[serde(default)]
+        version: Option<String>,
+        #[serde(default)]
+        host: Option<String>,
+        #[serde(default)]
+        recipe: Option<String>,
+        #[serde(default)]
+        args: BTreeMap<String, String>,
+    },
+}
+
+impl 清單條目 {
+    pub fn 配方名片(&self) -> anyhow::Result<配方名片> {
+        match self {
+            清單條目::簡寫(配方) => Ok(配方名片::from_str(配方)?),
+            清單條目::詳寫 {
+                name,
+                version,
+                host,
+                recipe,
+                args,
+            } => {
+                let 配方 = 配方名片::from_str(name)?;
+                let mut 配方參數 = 配方.配方參數;
+                配方參數.extend(args.clone());
+                Ok(配方名片 {
+                    版本: version.clone().or(配方.版本),
+                    倉庫域名: host.clone().or(配方.倉庫域名),
+                    配方名: recipe.clone().or(配方.配方名),
+                    配方參數,
+                    ..配方
+                })
+            }
+        }
+    }
+}
+
+impl 配方清單 {
+    pub fn 讀取(路徑: &Path) -> anyhow::Result<Self> {
+        let 內容 = std::fs::read_to_string(路徑)
+            .map_err(|e| anyhow!("讀取配方清單 {} 失敗: {}", 路徑.display(), e))?;
+        Self::解析(內容)
+    }
+
+    pub fn 讀取或新建(路徑: &Path) -> anyhow::Result<Self> {
+        if 路徑.exists() {
+            Self::讀取(路徑)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    fn 解析(原文: String) -> anyhow::Result<Self> {
+        let mut 清單 = if 原文.trim().is_empty() {
+            Self::default()
+        } else {
+            serde_yaml::from_str::<Self>(&原文)?
+        };
+        清單.原文 = 原文;
+        Ok(清單)
+    }
+
+    pub fn 寫入(&self, 路徑: &Path) -> anyhow::Result<()> {
+        std::fs::write(路徑, &self.原文)?;
+        Ok(())
+    }
+
+    pub fn 衆配方(&self) -> anyhow::Result<Vec<配方名片>> {
+        self.衆條目.iter().map(清單條目::配方名片).collect()
+    }
+
+    /// 加入配方. 若已有同一配方 (不論版本), 則就地改寫其版本, 不改變寫法;
+    /// 否則在列表末尾添一條簡寫. 返回清單是否有變.
+    pub fn 加入(&mut self, 配方: &配方名片) -> anyhow::Result<bool> {
+        let mut 衆行 = self.原文.lines().map(str::to_owned).collect::<Vec<_>>();
+        let mut 同一配方 = None;
+        for (i, 條目) in self.衆條目.iter().enumerate() {
+            let 舊配方 = 條目.配方名片()?;
+            if 舊配方.不論版本() == 配方.不論版本() {
+                if 舊配方 == *配方 {
+                    return Ok(false);
+                }
+                同一配方 = Some(i);
+                break;
+            }
+        }
+        match 同一配方 {
+            Some(i) => {
+                let 範圍 = self.條目所在的行(&衆行, i)?;
+                改寫條目版本(&mut 衆行, 範圍, &self.衆條目[i], 配方.版本.as_deref())?;
+            }
+            None => {
+                let (行號, 條目縮進) = match 找到列表(&衆行)? {
+                    Some(列表) => match 列表.衆條目.last() {
+                        Some(末條) => (末條.end, 縮進(&衆行[末條.start])),
+                        None => (列表.鍵 + 1, 2),
+                    },
+                    None => {
+                        衆行.push("recipes:".to_owned());
+                        (衆行.len(), 2)
+                    }
+                };
+                衆行.insert(行號, format!("{}- {}", " ".repeat(條目縮進), 配方));
+            }
+        }
+        *self = Self::解析(衆行.join("\n") + "\n")?;
+        Ok(true)
+    }
+
+    /// 移除同一配方 (不論版本) 的條目. 返回清單是否有變.
+    pub fn 移除(&mut self, 配方: &配方名片) -> anyhow::Result<bool> {
+        let mut 衆行 = self.原文.lines().map(str::to_owned).collect::<Vec<_>>();
+        let mut 有變 = false;
+        // 從後往前刪, 前面條目的行號不變
+        for (i, 條目) in self.衆條目.iter().enumerate().rev() {
+            if 條目.配方名片()?.不論版本() == 配方.不論版本() {
+                衆行.drain(self.條目所在的行(&衆行, i)?);
+                有變 = true;
+            }
+        }
+        if 有變 {
+            *self = Self::解析(衆行.join("\n") + "\n")?;
+        }
+        Ok(有變)
+    }
+
+    fn 條目所在的行(&self, 衆行: &[String], 序號: usize) -> anyhow::Result<Range<usize>> {
+        let 衆條目 = 找到列表(衆行)?.map(|列表| 列表.衆條目).unwrap_or_default();
+        if 衆條目.len() != self.衆條目.len() {
+            bail!("配方清單的 recipes 寫法特殊, 請手動修改");
+        }
+        Ok(衆條目[序號].clone())
+    }
+}
+
+/// `recipes` 列表在清單原文中的位置: 鍵所在的行, 及每一條目所佔的行.
+struct 列表位置 {
+    鍵: usize,
+    衆條目: Vec<Range<usize>>,
+}
+
+/// 只認逐行寫的列表. 條目之後的空行和註釋不算在條目內.
+fn 找到列表(衆行: &[String]) -> anyhow::Result<Option<列表位置>> {
+    let Some(鍵) = 衆行.iter().position(|行| 行.starts_with("recipes:")) else {
+        return Ok(None);
+    };
+    if !去掉註釋(&衆行[鍵]["recipes:".len()..]).trim().is_empty() {
+        bail!("配方清單的 recipes 要逐行寫出條目, 才能自動修改");
+    }
+    let mut 衆條目 = Vec::<Range<usize>>::new();
+    let mut 條目縮進 = None;
+    for (i, 行) in 衆行.iter().enumerate().skip(鍵 + 1) {
+        if 去掉註釋(行).trim().is_empty() {
+            continue;
+        }
+        let 本行縮進 = 縮進(行);
+        let 內容 = &行[本行縮進..];
+        let 是條目 = 內容 == "-" || 內容.starts_with("- ");
+        if 本行縮進 == 0 && !是條目 {
+            // 下一個頂層的鍵
+            break;
+        }
+        if 是條目 && 條目縮進.unwrap_or(本行縮進) == 本行縮進 {
+            條目縮進 = Some(本行縮進);
+            衆條目.push(i..i + 1);
+        } else if let Some(當前條目) = 衆條目.last_mut() {
+            當前條目.end = i + 1;
+        }
+    }
+    Ok(Some(列表位置 { 鍵, 衆條目 }))
+}
+
+/// 按條目原有的寫法改版本: 簡寫改其中的 `@版本`; 詳寫有 version 的改 version,
+/// 沒有的改 name 中的 `@版本`.
+fn 改寫條目版本(
+    衆行: &mut Vec<String>,
+    範圍: Range<usize>,
+    條目: &清單條目,
+    版本: Option<&str>,
+) -> anyhow::Result<()> {
+    let 找不到 = || anyhow!("配方清單中的條目寫法特殊, 請手動修改: {}", 衆行[範圍.start]);
+    match (條目, 版本) {
+        (清單條目::簡寫(_), _) => {
+            let 行 = &衆行[範圍.start];
+            let 起點 = 行.len() - 行[縮進(行) + 1..].trim_start().len();
+            改寫值(衆行, (範圍.start, 起點), |寫法| {
+                改寫版本(寫法, 版本)
+            });
+        }
+        (
+            清單條目::詳寫 {
+                version: Some(_), ..
+            },
+            Some(版本),
+        ) => {
+            let 位置 = 找鍵(衆行, &範圍, "version").ok_or_else(找不到)?;
+            改寫值(衆行, 位置, |_| 版本.to_owned());
+        }
+        (
+            清單條目::詳寫 {
+                version: Some(_), ..
+            },
+            None,
+        ) => {
+            // 刪去 version 一行, name 中若有版本也要去掉
+            let (版本行, _) = 找鍵(衆行, &範圍, "version").ok_or_else(找不到)?;
+            if 版本行 == 範圍.start {
+                return Err(找不到());
+            }
+            let 位置 = 找鍵(衆行, &範圍, "name").ok_or_else(找不到)?;
+            改寫值(衆行, 位置, |寫法| 改寫版本(寫法, None));
+            衆行.remove(版本行);
+        }
+        (清單條目::詳寫 { .. }, _) => {
+            let 位置 = 找鍵(衆行, &範圍, "name").ok_or_else(找不到)?;
+            改寫值(衆行, 位置, |寫法| 改寫版本(寫法, 版本));
+        }
+    }
+    Ok(())
+}
+
+/// 詳寫的條目中 `鍵:` 所在的行, 及其值的起點. 只找與 `- ` 之後同一縮進的鍵.
+fn 找鍵(衆行: &[String], 範圍: &Range<usize>, 鍵: &str) -> Option<(usize, usize)> {
+    let 鍵縮進 = 縮進(&衆行[範圍.start]) + 2;
+    範圍.clone().find_map(|i| {
+        let 行 = &衆行[i];
+        let 起點 = if i == 範圍.start {
+            鍵縮進
+        } else {
+            縮進(行)
+        };
+        let 其餘 = 行.get(起點..)?.strip_prefix(鍵)?.strip_prefix(':')?;
+        (起點 == 鍵縮進 && (其餘.is_empty() || 其餘.starts_with(' ')))
+            .then_some((i, 行.len() - 其餘.trim_start().len()))
+    })
+}
+
+/// 改寫 `前綴 值 # 註釋` 一行中的值, `位置` 爲行號和值的起點. 保留前綴, 引號和註釋.
+fn 改寫值(衆行: &mut [String], 位置: (usize, usize), 改寫: impl FnOnce(&str) -> String) {
+    let (行號, 起點) = 位置;
+    let 行 = &衆行[行號];
+    let 其餘 = &行[起點..];
+    let 值 = 去掉註釋(其餘).trim_end();
+    let 註釋 = &其餘[值.len()..];
+    let (引號, 值) = match 值.chars().next() {
+        Some(引號 @ ('"' | '\'')) if 值.len() >= 2 && 值.ends_with(引號) => {
+            (&值[..1], &值[1..值.len() - 1])
+        }
+        _ => ("", 值),
+    };
+    衆行[行號] = format!("{}{引號}{}{引號}{註釋}", &行[..起點], 改寫(值));
+}
+
+fn 去掉註釋(s: &str) -> &str {
+    if s.trim_start().starts_with('#') {
+        ""
+    } else {
+        s.find(" #").map_or(s, |i| &s[..i])
+    }
+}
+
+fn 縮進(行: &str) -> usize {
+    行.len() - 行.trim_start_matches(' ').len()
+}
+
+/// 命令行上沒有指定配方時, 取配方清單中的全部配方.
+pub fn 缺省取清單中的配方(
+    衆配方: &[配方名片],
+    工作場地: &Path,
+) -> anyhow::Result<Vec<配方名片>> {
+    if !衆配方.is_empty() {
+        return Ok(衆配方.to_vec());
+    }
+    let 清單路徑 = 工作場地.join(清單文件名);
+    if !清單路徑.exists() {
+        anyhow::bail!("沒有指定配方, 也沒有配方清單 {}", 清單路徑.display());
+    }
+    配方清單::讀取(&清單路徑)?.衆配方()
+}
+
+/// 把配方記入配方清單. 清單沒有變化就不重寫.
+pub fn 記入配方清單(衆配方: &[配方名片], 工作場地: &Path) -> anyhow::Result<()> {
+    let 清單路徑 = 工作場地.join(清單文件名);
+    let mut 清單 = 配方清單::讀取或新建(&清單路徑)?;
+    let mut 有變 = false;
+    for 配方 in 衆配方 {
+        有變 |= 清單.加入(配方)?;
+    }
+    if 有變 {
+        清單.寫入(&清單路徑)?;
+    }
+    Ok(())
+}
+
+/// 把配方移出配方清單. 沒有清單或清單中沒有這些配方, 則不動.
+pub fn 移出配方清單(衆配方: &[配方名片], 工作場地: &Path) -> anyhow::Result<()> {
+    let 清單路徑 = 工作場地.join(清單文件名);
+    if !清單路徑.exists() {
+        return Ok(());
+    }
+    let mut 清單 = 配方清單::讀取(&清單路徑)?;
+    let mut 有變 = false;
+    for 配方 in 衆配方 {
+        有變 |= 清單.移除(配方)?;
+    }
+    if 有變 {
+        清單.寫入(&清單路徑)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::配方;
+
+    #[test]
+    fn 測試配方清單() {
+        let 清單: 配方清單 = serde_yaml::from_str(
+            r#"
+recipes:
+  - luna-pinyin
+  - gitee.com:team/rime-foo@dev
+  - name: emoji
+    version: master
+    recipe: customize
+    args:
+      schema: luna_pinyin
+"#,
+        )
+        .unwrap();
+        let 衆配方 = 清單
+            .衆配方()
+            .unwrap()
+            .iter()
+            .map(配方名片::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            衆配方,
+            [
+                "rime/rime-luna-pinyin",
+                "gitee.com:team/rime-foo@dev",
+                "rime/rime-emoji@master:customize:schema=luna_pinyin",
+            ]
+        );
+    }
+
+    #[test]
+    fn 測試配方清單_加入() {
+        let mut 清單 = 配方清單::default();
+        assert!(清單.加入(&配方("luna-pinyin")).unwrap());
+        assert!(清單
+            .加入(&配方("emoji:customize:schema=luna_pinyin"))
+            .unwrap());
+        assert!(清單.加入(&配方("luna-pinyin@1.0")).unwrap());
+        assert!(!清單.加入(&配方("luna-pinyin@1.0")).unwrap());
+        assert!(清單
+            .加入(&配方("emoji:customize:schema=terra_pinyin"))
+            .unwrap());
+        assert_eq!(
+            清單.原文,
+            r#"recipes:
+  - rime/rime-luna-pinyin@1.0
+  - rime/rime-emoji:customize:schema=luna_pinyin
+  - rime/rime-emoji:customize:schema=terra_pinyin
+"#
+        );
+    }
+
+    #[test]
+    fn 測試配方清單_加入_保留原來的寫法() {
+        let mut 清單 = 配方清單::解析(
+            r#"# 常用的配方
+recipes:
+  - luna-pinyin # 朙月拼音
+  - "gitee.com:team/rime-foo@dev"
+  - name: emoji
+    version: master # 跟蹤主幹
+    recipe: customize
+    args:
+      schema: luna_pinyin
+  - name: prelude@1.0
+    host: gitee.com
+
+# 以下是別的設定
+other: true
+"#
+            .to_owned(),
+        )
+        .unwrap();
+        assert!(清單.加入(&配方("luna-pinyin@1.0")).unwrap());
+        assert!(清單.加入(&配方("gitee.com:team/rime-foo")).unwrap());
+        assert!(清單
+            .加入(&配方("emoji@v2:customize:schema=luna_pinyin"))
+            .unwrap());
+        assert!(清單.加入(&配方("gitee.com:rime/rime-prelude@2.0")).unwrap());
+        assert!(清單.加入(&配方("cangjie")).unwrap());
+        assert_eq!(
+            清單.原文,
+            r#"# 常用的配方
+recipes:
+  - luna-pinyin@1.0 # 朙月拼音
+  - "gitee.com:team/rime-foo"
+  - name: emoji
+    version: v2 # 跟蹤主幹
+    recipe: customize
+    args:
+      schema: luna_pinyin
+  - name: prelude@2.0
+    host: gitee.com
+  - rime/rime-cangjie
+
+# 以下是別的設定
+other: true
+"#
+        );
+
+        // 去掉詳寫的版本
+        assert!(清單
+            .加入(&配方("emoji:customize:schema=luna_pinyin"))
+            .unwrap());
+        assert!(清單
+            .原文
+            .contains("  - name: emoji\n    recipe: customize\n"));
+        assert_eq!(
+            清單.衆配方().unwrap()[2].to_string(),
+            "rime/rime-emoji:customize:schema=luna_pinyin"
+        );
+    }
+
+    #[test]
+    fn 測試配方清單_移除() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let 工作場地 = tmp_dir.path();
+        let 清單路徑 = 工作場地.join(清單文件名);
+        let 內容 = "# 常用的配方\nrecipes:\n  - luna-pinyin@1.0 # 固定版本\n  - emoji\n";
+        std::fs::write(&清單路徑, 內容).unwrap();
+
+        // 沒有變化, 不重寫清單
+        記入配方清單(&[], 工作場地).unwrap();
+        記入配方清單(&[配方("luna-pinyin@1.0")], 工作場地).unwrap();
+        移出配方清單(&[配方("cangjie")], 工作場地).unwrap();
+        assert_eq!(std::fs::read_to_string(&清單路徑).unwrap(), 內容);
+
+        移出配方清單(&[配方("luna-pinyin")], 工作場地).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&清單路徑).unwrap(),
+            "# 常用的配方\nrecipes:\n  - emoji\n"
+        );
+    }
+}
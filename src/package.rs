@@ -1,8 +1,8 @@
 use std::collections::HashMap;
 use std::fmt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use crate::recipe::配方名片;
+use crate::recipe::{倉庫來源, 配方名片};
 
 #[derive(Clone)]
 pub struct 配方包<'a> {
@@ -13,22 +13,71 @@ pub struct 配方包<'a> {
 
 impl 配方包<'_> {
     pub fn 倉庫地址(&self) -> String {
+        match &self.配方.來源 {
+            Some(倉庫來源::網址(網址)) => return 網址.clone(),
+            Some(倉庫來源::本地(路徑)) => return 路徑.display().to_string(),
+            None => {}
+        }
         format!(
             "https://{}/{}/{}.git",
-            self.倉庫域名.unwrap_or("github.com"),
+            self.配方
+                .倉庫域名
+                .as_deref()
+                .or(self.倉庫域名)
+                .unwrap_or("github.com"),
             self.配方.方家,
             self.配方.名字
         )
     }
 
-    pub fn 倉庫分支(&self) -> Option<&str> {
+    /// 分支, 標籤或提交
+    pub fn 倉庫版本(&self) -> Option<&str> {
         self.配方.版本.as_deref()
     }
 
-    pub fn 本地路徑(&self) -> PathBuf {
-        ["pkg", self.配方.方家.as_str(), self.配方.名字.as_str()]
-            .iter()
-            .collect()
+    /// 本地目錄直接使用, 不必下載.
+    pub fn 是本地目錄(&self) -> bool {
+        matches!(self.配方.來源, Some(倉庫來源::本地(_)))
+    }
+
+    /// 配方包下載到工作場地的 `pkg/方家/名字`.
+    pub fn 本地路徑(&self, 工作場地: &Path) -> PathBuf {
+        if let Some(倉庫來源::本地(路徑)) = &self.配方.來源 {
+            return 路徑.clone();
+        }
+        工作場地
+            .join("pkg")
+            .join(&self.配方.方家)
+            .join(&self.配方.名字)
+    }
+
+    /// 配方包相對於工作場地的目錄. 同一目錄即同一配方包, 不論配方怎麼寫.
+    pub fn 相對路徑(&self) -> PathBuf {
+        self.本地路徑(Path::new(""))
+    }
+
+    /// 工作場地中下載了的配方包, 即 `pkg/<方家>/<名字>` 下的每個目錄, 按名字排序.
+    pub fn 列出下載的配方包(
+        工作場地: &Path
+    ) -> anyhow::Result<Vec<配方包<'static>>> {
+        let mut 衆配方包 = vec![];
+        for 方家 in 子目錄(&工作場地.join("pkg"))? {
+            for 名字 in 子目錄(&工作場地.join("pkg").join(&方家))? {
+                衆配方包.push(配方包 {
+                    配方: 配方名片 {
+                        方家: 方家.clone(),
+                        名字,
+                        版本: None,
+                        配方名: None,
+                        配方參數: Default::default(),
+                        倉庫域名: None,
+                        來源: None,
+                    },
+                    倉庫域名: None,
+                });
+            }
+        }
+        Ok(衆配方包)
     }
 
     pub fn 按倉庫分組<'a>(
@@ -37,10 +86,7 @@ impl 配方包<'_> {
     ) -> HashMap<配方名片, Vec<配方包<'a>>> {
         let mut 按倉庫分組 = HashMap::new();
         衆配方.iter().for_each(|配方| {
-            let 包名 = 配方名片 {
-                版本: None,
-                ..配方.clone()
-            };
+            let 包名 = 配方.倉庫名片();
             按倉庫分組
                 .entry(包名)
                 .or_insert_with(Vec::new)
@@ -53,10 +99,33 @@ impl 配方包<'_> {
     }
 }
 
+/// 目錄下的子目錄名, 按名字排序. 目錄不存在則爲空.
+/// 略去 `.` 開頭的, 如下載中的臨時目錄.
+fn 子目錄(目錄: &Path) -> anyhow::Result<Vec<String>> {
+    if !目錄.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut 衆子目錄 = vec![];
+    for 條目 in std::fs::read_dir(目錄)? {
+        let 條目 = 條目?;
+        if 條目.file_type()?.is_dir() {
+            if let Some(名字) = 條目
+                .file_name()
+                .to_str()
+                .filter(|名字| !名字.starts_with('.'))
+            {
+                衆子目錄.push(名字.to_owned());
+            }
+        }
+    }
+    衆子目錄.sort();
+    Ok(衆子目錄)
+}
+
 impl fmt::Display for 配方包<'_> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match &self.倉庫分支() {
-            Some(分支) => write!(f, "{}@{}", self.倉庫地址(), 分支),
+        match &self.倉庫版本() {
+            Some(版本) => write!(f, "{}@{}", self.倉庫地址(), 版本),
             None => write!(f, "{}", self.倉庫地址()),
         }
     }
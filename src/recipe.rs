@@ -1,41 +1,382 @@
+use serde::Deserialize;
+use serde_yaml::Mapping;
+use std::collections::BTreeMap;
 use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-#[derive(Clone, Eq, Hash, PartialEq)]
+/// 指定配方的寫法與 plum 相同: `方家/名字@版本:配方名:紐=值,紐=值`
+///
+/// `方家/名字` 前可加倉庫域名, 如 `gitee.com:方家/名字`;
+/// 也可以換成倉庫的網址, 或者本地的配方目錄.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
 pub struct 配方名片 {
     pub 方家: String,
     pub 名字: String,
     pub 版本: Option<String>,
+    /// 配方包中的 `<配方名>.recipe.yaml`, 不指定則用 `recipe.yaml`
+    pub 配方名: Option<String>,
+    pub 配方參數: BTreeMap<String, String>,
+    /// 不指定則用命令行參數 `--host`, 再不指定則用 github.com
+    pub 倉庫域名: Option<String>,
+    /// 不指定則按方家和名字在倉庫域名下找
+    pub 來源: Option<倉庫來源>,
+}
+
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub enum 倉庫來源 {
+    /// git 倉庫的網址, 包括 https, ssh, `file://` 及 `git@host:path` 形式
+    網址(String),
+    /// 本地的配方目錄, 直接從中安裝
+    本地(PathBuf),
+}
+
+impl 配方名片 {
+    /// 不論版本, 用來判斷是否同一配方.
+    pub fn 不論版本(&self) -> Self {
+        Self {
+            版本: None,
+            ..self.clone()
+        }
+    }
+
+    /// 只保留倉庫的名字, 用來合併同一倉庫的配方.
+    pub fn 倉庫名片(&self) -> Self {
+        Self {
+            方家: self.方家.clone(),
+            名字: self.名字.clone(),
+            版本: None,
+            配方名: None,
+            配方參數: BTreeMap::new(),
+            倉庫域名: self.倉庫域名.clone(),
+            來源: self.來源.clone(),
+        }
+    }
 }
 
 impl fmt::Display for 配方名片 {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}/{}", self.方家, self.名字)?;
+        match &self.來源 {
+            Some(倉庫來源::網址(網址)) => write!(f, "{}", 網址)?,
+            Some(倉庫來源::本地(路徑)) => write!(f, "{}", 路徑.display())?,
+            None => {
+                if let Some(域名) = &self.倉庫域名 {
+                    write!(f, "{}:", 域名)?;
+                }
+                write!(f, "{}/{}", self.方家, self.名字)?
+            }
+        }
         if let Some(版本) = &self.版本 {
             write!(f, "@{}", 版本)?;
         }
+        if self.配方名.is_some() || !self.配方參數.is_empty() {
+            write!(f, ":{}", self.配方名.as_deref().unwrap_or(""))?;
+        }
+        if !self.配方參數.is_empty() {
+            let 參數 = self
+                .配方參數
+                .iter()
+                .map(|(紐, 值)| format!("{紐}={值}"))
+                .collect::<Vec<_>>()
+                .join(",");
+            write!(f, ":{}", 參數)?;
+        }
         Ok(())
     }
 }
 
-impl From<&str> for 配方名片 {
-    fn from(source: &str) -> Self {
-        // 有冇版本?
-        let (全名, 版本) = source
-            .split_once('@')
-            .map(|(全名, 版本)| (全名, Some(版本.to_owned())))
+impl FromStr for 配方名片 {
+    type Err = 配方名片錯誤;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        if source.trim().is_empty() {
+            return Err(配方名片錯誤::空白);
+        }
+        // 哪個網站?
+        let (倉庫域名, source) = match 域名前綴冒號(source) {
+            Some(i) => (Some(source[..i].to_owned()), &source[i + 1..]),
+            None => (None, source),
+        };
+        // 網址和路徑中可能有 `:` 和 `@`, 從倉庫路徑開始處找分隔符
+        let 類型 = 倉庫寫法::判斷(source);
+        let 起點 = 類型.路徑起點(source);
+        // 要哪個配方?
+        let (倉庫, 配方) = source[起點..]
+            .find(':')
+            .map(|i| (&source[..起點 + i], Some(&source[起點 + i + 1..])))
             .unwrap_or((source, None));
+        let (配方名, 參數) = match 配方 {
+            Some(配方) => 配方.split_once(':').unwrap_or((配方, "")),
+            None => ("", ""),
+        };
+        if 配方.is_some() && 配方名.is_empty() && 參數.is_empty() {
+            return Err(配方名片錯誤::缺少("配方名"));
+        }
+        let 配方名 = if 配方名.is_empty() {
+            None
+        } else {
+            檢查字符("配方名", 配方名, 是名字字符)?;
+            Some(配方名.to_owned())
+        };
+        let 配方參數 = 參數
+            .split(',')
+            .filter(|項| !項.is_empty())
+            .map(|項| match 項.split_once('=') {
+                Some((紐, 值)) if !紐.is_empty() && 紐.chars().all(是名字字符) => {
+                    Ok((紐.to_owned(), 值.to_owned()))
+                }
+                _ => Err(配方名片錯誤::無效參數(項.to_owned())),
+            })
+            .collect::<Result<_, _>>()?;
+        // 有冇版本? 本地目錄不分版本
+        let 版本位置 = match 類型 {
+            倉庫寫法::本地 => None,
+            _ => 倉庫[起點..].find('@').map(|i| 起點 + i),
+        };
+        let (全名, 版本) = match 版本位置 {
+            Some(i) if i + 1 == 倉庫.len() => return Err(配方名片錯誤::缺少("版本")),
+            Some(i) => {
+                let 版本 = &倉庫[i + 1..];
+                檢查字符("版本", 版本, 是版本字符)?;
+                (&倉庫[..i], Some(版本.to_owned()))
+            }
+            None => (倉庫, None),
+        };
         // 哪位方家?
-        let (方家, 名字) = 全名
-            .split_once('/')
-            .map(|(方家, 名字)| (方家.to_owned(), 名字.to_owned()))
-            .unwrap_or(("rime".to_owned(), 規範的配方名字(全名)));
-        Self {
-            方家, 名字, 版本
+        let (方家, 名字, 來源) = match 類型 {
+            倉庫寫法::簡稱 => {
+                let (方家, 名字) = match 全名.split_once('/') {
+                    Some((_, 名字)) if 名字.contains('/') => {
+                        return Err(配方名片錯誤::多餘的斜線(全名.to_owned()))
+                    }
+                    Some((方家, 名字)) => (方家.to_owned(), 名字.to_owned()),
+                    None => ("rime".to_owned(), 規範的配方名字(全名)),
+                };
+                if 方家.is_empty() {
+                    return Err(配方名片錯誤::缺少("方家"));
+                }
+                if 名字.is_empty() || 全名.is_empty() {
+                    return Err(配方名片錯誤::缺少("名字"));
+                }
+                檢查字符("方家", &方家, 是名字字符)?;
+                檢查名字("方家", &方家)?;
+                檢查名字("名字", &名字)?;
+                (方家, 名字, None)
+            }
+            倉庫寫法::網址 => {
+                let (方家, 名字) = 從路徑取名(全名, "local");
+                if let Some(字符) = 全名.chars().find(|c| c.is_whitespace()) {
+                    return Err(配方名片錯誤::非法字符 {
+                        部分: "網址", 字符
+                    });
+                }
+                // 方家取自網址, 不合規矩的已換作 local
+                檢查名字("名字", &名字)?;
+                (方家, 名字, Some(倉庫來源::網址(全名.to_owned())))
+            }
+            倉庫寫法::本地 => {
+                let (方家, 名字) = 從路徑取名(全名, "local");
+                (方家, 名字, Some(倉庫來源::本地(PathBuf::from(全名))))
+            }
+        };
+        if 名字.is_empty() {
+            return Err(配方名片錯誤::缺少("名字"));
+        }
+        檢查字符("名字", &名字, 是名字字符)?;
+        Ok(Self {
+            方家,
+            名字,
+            版本,
+            配方名,
+            配方參數,
+            倉庫域名,
+            來源,
+        })
+    }
+}
+
+/// 把配方名片寫法中的版本換作 `版本`, 其餘一字不改. `版本` 爲 `None` 則去掉版本.
+/// 如 `luna-pinyin:custom` 改作 `luna-pinyin@1.0:custom`.
+pub fn 改寫版本(source: &str, 版本: Option<&str>) -> String {
+    let 前綴 = 域名前綴冒號(source).map_or(0, |i| i + 1);
+    let 類型 = 倉庫寫法::判斷(&source[前綴..]);
+    if 類型 == 倉庫寫法::本地 {
+        // 本地目錄不分版本
+        return source.to_owned();
+    }
+    let 起點 = 前綴 + 類型.路徑起點(&source[前綴..]);
+    let 倉庫結尾 = source[起點..].find(':').map_or(source.len(), |i| 起點 + i);
+    let 版本位置 = source[起點..倉庫結尾]
+        .find('@')
+        .map_or(倉庫結尾, |i| 起點 + i);
+    let 版本 = 版本.map(|版本| format!("@{版本}")).unwrap_or_default();
+    format!("{}{}{}", &source[..版本位置], 版本, &source[倉庫結尾..])
+}
+
+/// 配方名片中倉庫部分的寫法
+#[derive(Clone, Copy, PartialEq)]
+enum 倉庫寫法 {
+    /// `方家/名字` 或 `名字`
+    簡稱,
+    /// `scheme://host/path` 或 `user@host:path`
+    網址,
+    /// `./path`, `/path` 等
+    本地,
+}
+
+impl 倉庫寫法 {
+    fn 判斷(source: &str) -> Self {
+        if 網址協議結尾(source).is_some() || scp_式網址冒號(source).is_some() {
+            倉庫寫法::網址
+        } else if source.starts_with(['.', '/', '~', '\\']) || 有盤符(source) {
+            倉庫寫法::本地
+        } else {
+            倉庫寫法::簡稱
+        }
+    }
+
+    /// 倉庫路徑在 source 中的起始位置, 其前的 `:` 和 `@` 不是分隔符.
+    fn 路徑起點(self, source: &str) -> usize {
+        match self {
+            倉庫寫法::網址 => {
+                if let Some(協議結尾) = 網址協議結尾(source) {
+                    source[協議結尾..]
+                        .find('/')
+                        .map_or(source.len(), |i| 協議結尾 + i)
+                } else {
+                    scp_式網址冒號(source).map_or(0, |i| i + 1)
+                }
+            }
+            倉庫寫法::本地 if 有盤符(source) => 2,
+            _ => 0,
+        }
+    }
+}
+
+/// `域名:方家/名字` 中 `:` 的位置. 域名中須有 `.`, 以與 `名字:配方名` 區分.
+fn 域名前綴冒號(source: &str) -> Option<usize> {
+    let 冒號 = source.find(':')?;
+    let 域名 = &source[..冒號];
+    let 全名 = source[冒號 + 1..].split([':', '@']).next().unwrap_or("");
+    let 域名合法 = 域名.contains('.')
+        && 域名
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.'));
+    let 全名合法 = 全名.starts_with(是名字字符) && 全名.contains('/');
+    (域名合法 && 全名合法).then_some(冒號)
+}
+
+/// `scheme://` 之後的位置
+fn 網址協議結尾(source: &str) -> Option<usize> {
+    let i = source.find("://")?;
+    let 協議 = &source[..i];
+    (!協議.is_empty() && 協議.chars().all(|c| c.is_ascii_alphanumeric() || c == '+'))
+        .then_some(i + 3)
+}
+
+/// `user@host:path` 中 `:` 的位置. 須與 `名字@版本:配方名` 區分, 故要求 path 中有 `/`.
+fn scp_式網址冒號(source: &str) -> Option<usize> {
+    let 冒號 = source.find(':')?;
+    let (用戶, 主機) = source[..冒號].split_once('@')?;
+    let 合法 = |s: &str| !s.is_empty() && s.chars().all(是名字字符);
+    let 路徑 = source[冒號 + 1..].split([':', '@']).next().unwrap_or("");
+    (合法(用戶) && 合法(主機) && 路徑.contains('/')).then_some(冒號)
+}
+
+/// Windows 路徑 `C:\\`
+fn 有盤符(source: &str) -> bool {
+    let 字節 = source.as_bytes();
+    字節.len() >= 3
+        && 字節[0].is_ascii_alphabetic()
+        && 字節[1] == b':'
+        && matches!(字節[2], b'\\' | b'/')
+}
+
+/// 取網址或路徑的最後兩級作方家和名字, 去掉 `.git` 後綴.
+fn 從路徑取名(路徑: &str, 默認方家: &str) -> (String, String) {
+    let mut 衆段 = 路徑
+        .trim_end_matches(['/', '\\'])
+        .rsplit(['/', '\\', ':', '@'])
+        .filter(|段| !段.is_empty() && *段 != ".");
+    let 名字 = 衆段.next().unwrap_or("");
+    let 名字 = 名字.strip_suffix(".git").unwrap_or(名字);
+    let 方家 = 衆段
+        .next()
+        .filter(|段| 段.chars().all(是名字字符) && !段.starts_with('.'))
+        .unwrap_or(默認方家);
+    (方家.to_owned(), 名字.to_owned())
+}
+
+#[derive(Debug, PartialEq)]
+pub enum 配方名片錯誤 {
+    空白,
+    缺少(&'static str),
+    多餘的斜線(String),
+    非法字符 {
+        部分: &'static str,
+        字符: char,
+    },
+    點開頭 {
+        部分: &'static str,
+        名字: String,
+    },
+    無效參數(String),
+}
+
+impl fmt::Display for 配方名片錯誤 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            配方名片錯誤::空白 => write!(f, "配方名片是空白的"),
+            配方名片錯誤::缺少(部分) => write!(f, "配方名片缺少{}", 部分),
+            配方名片錯誤::多餘的斜線(全名) => {
+                write!(f, "應寫作 方家/名字, 而不是 {}", 全名)
+            }
+            配方名片錯誤::非法字符 { 部分, 字符 } => {
+                write!(f, "{}中有非法字符 {:?}", 部分, 字符)
+            }
+            配方名片錯誤::點開頭 { 部分, 名字 } => {
+                write!(f, "{}不能以 . 開頭: {}", 部分, 名字)
+            }
+            配方名片錯誤::無效參數(參數) => {
+                write!(f, "配方參數應寫作 紐=值, 而不是 {}", 參數)
+            }
         }
     }
 }
 
-fn 規範的配方名字(名字: &str) -> String {
+impl std::error::Error for 配方名片錯誤 {}
+
+fn 是名字字符(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')
+}
+
+/// 方家和名字是 `pkg/` 下的目錄名: `.` 和 `..` 會指向 `pkg/` 本身或其上級.
+fn 檢查名字(部分: &'static str, 名字: &str) -> Result<(), 配方名片錯誤> {
+    if 名字.starts_with('.') {
+        return Err(配方名片錯誤::點開頭 {
+            部分,
+            名字: 名字.to_owned(),
+        });
+    }
+    Ok(())
+}
+
+fn 是版本字符(c: char) -> bool {
+    是名字字符(c) || matches!(c, '/' | '+')
+}
+
+fn 檢查字符(
+    部分: &'static str,
+    內容: &str,
+    合法: fn(char) -> bool,
+) -> Result<(), 配方名片錯誤> {
+    match 內容.chars().find(|&c| !合法(c)) {
+        Some(字符) => Err(配方名片錯誤::非法字符 { 部分, 字符 }),
+        None => Ok(()),
+    }
+}
+
+pub fn 規範的配方名字(名字: &str) -> String {
     // 規範規範, 要包含 rime 數據倉庫前綴
     if 名字.starts_with("rime-") {
         名字.to_owned()
@@ -44,13 +385,141 @@ fn 規範的配方名字(名字: &str) -> String {
     }
 }
 
+/// 配方包中的 `recipe.yaml`, 格式與 plum 相同.
+#[derive(Debug, Deserialize)]
+pub struct 配方文件 {
+    #[serde(rename = "recipe")]
+    pub 說明: 配方說明,
+    #[serde(rename = "install_files", default)]
+    pub 安裝文件: Option<文件模式>,
+    #[serde(rename = "patch_files", default)]
+    pub 補丁文件: BTreeMap<String, 補丁內容>,
+    /// 須先安裝的配方, 寫法同命令行參數
+    #[serde(rename = "dependencies", default)]
+    pub 依賴: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct 配方說明 {
+    #[serde(rename = "Rx")]
+    pub 配方: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    pub description: Option<String>,
+}
+
+/// 可以寫成空白分隔的一串, 也可以寫成列表.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum 文件模式 {
+    一串(String),
+    列表(Vec<String>),
+}
+
+/// 一個文件的補丁, 可以寫成字典, 也可以寫成字典的列表.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum 補丁內容 {
+    字典(Mapping),
+    列表(Vec<Mapping>),
+}
+
+impl 配方文件 {
+    pub fn 讀取(路徑: &Path, 參數: &BTreeMap<String, String>) -> anyhow::Result<Self> {
+        let 內容 = std::fs::read_to_string(路徑)?;
+        Self::代入參數解析(&內容, 參數)
+    }
+
+    /// 先按 `recipe/args` 補全參數的默認值, 再把 `${紐}` 代換成參數值.
+    pub fn 代入參數解析(
+        內容: &str, 參數: &BTreeMap<String, String>
+    ) -> anyhow::Result<Self> {
+        let 原文 = Self::解析(內容)?;
+        let mut 全部參數 = 參數.clone();
+        for 聲明 in &原文.說明.args {
+            let (紐, 默認值) = 聲明
+                .split_once('=')
+                .map(|(紐, 值)| (紐, Some(值)))
+                .unwrap_or((聲明.as_str(), None));
+            if !全部參數.contains_key(紐) {
+                let 默認值 =
+                    默認值.ok_or(anyhow::anyhow!("配方 {} 缺少參數: {}", 原文.說明.配方, 紐))?;
+                全部參數.insert(紐.to_owned(), 默認值.to_owned());
+            }
+        }
+        Self::解析(&代入參數(內容, &全部參數)?)
+    }
+
+    pub fn 解析(內容: &str) -> anyhow::Result<Self> {
+        Ok(serde_yaml::from_str(內容)?)
+    }
+}
+
+impl 配方文件 {
+    pub fn 衆依賴(&self) -> anyhow::Result<Vec<配方名片>> {
+        self.依賴
+            .iter()
+            .map(|依賴| {
+                配方名片::from_str(依賴)
+                    .map_err(|e| anyhow::anyhow!("配方 {} 的依賴 {}: {}", self.說明.配方, 依賴, e))
+            })
+            .collect()
+    }
+}
+
+impl 文件模式 {
+    pub fn 衆模式(&self) -> Vec<&str> {
+        match self {
+            文件模式::一串(模式) => 模式.split_whitespace().collect(),
+            文件模式::列表(衆模式) => 衆模式.iter().map(String::as_str).collect(),
+        }
+    }
+}
+
+impl 補丁內容 {
+    /// 按書寫順序列出補丁, 即 `__patch` 列表中的各項.
+    pub fn 衆字典(&self) -> &[Mapping] {
+        match self {
+            補丁內容::字典(字典) => std::slice::from_ref(字典),
+            補丁內容::列表(衆字典) => 衆字典.as_slice(),
+        }
+    }
+}
+
+/// 代換 `${紐}` 和 `${紐:-默認值}`.
+fn 代入參數(內容: &str, 參數: &BTreeMap<String, String>) -> anyhow::Result<String> {
+    let mut 結果 = String::with_capacity(內容.len());
+    let mut 餘下 = 內容;
+    while let Some(開始) = 餘下.find("${") {
+        結果.push_str(&餘下[..開始]);
+        let 變量 = &餘下[開始 + 2..];
+        let 結束 = 變量
+            .find('}')
+            .ok_or(anyhow::anyhow!("參數引用缺少 '}}': {}", &餘下[開始..]))?;
+        let (紐, 默認值) = 變量[..結束]
+            .split_once(":-")
+            .map(|(紐, 值)| (紐, Some(值)))
+            .unwrap_or((&變量[..結束], None));
+        let 值 = 參數
+            .get(紐)
+            .map(String::as_str)
+            .or(默認值)
+            .ok_or(anyhow::anyhow!("缺少參數: {}", 紐))?;
+        結果.push_str(值);
+        餘下 = &變量[結束 + 1..];
+    }
+    結果.push_str(餘下);
+    Ok(結果)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use serde_yaml::Value;
 
     #[test]
     fn 測試配方名片_姓名全不帶版本() {
-        let 配方 = 配方名片::from("lotem/rime-zhengma");
+        let 配方 = 配方名片::from_str("lotem/rime-zhengma").unwrap();
         assert_eq!(配方.方家, "lotem");
         assert_eq!(配方.名字, "rime-zhengma");
         assert_eq!(配方.版本, None);
@@ -58,7 +527,7 @@ mod tests {
 
     #[test]
     fn 測試配方名片_姓名全帶版本() {
-        let 配方 = 配方名片::from("lotem/rime-octagram-data@hant");
+        let 配方 = 配方名片::from_str("lotem/rime-octagram-data@hant").unwrap();
         assert_eq!(配方.方家, "lotem");
         assert_eq!(配方.名字, "rime-octagram-data");
         assert_eq!(配方.版本, Some("hant".to_owned()));
@@ -66,7 +535,7 @@ mod tests {
 
     #[test]
     fn 測試配方名片_只有名字() {
-        let 配方 = 配方名片::from("luna-pinyin");
+        let 配方 = 配方名片::from_str("luna-pinyin").unwrap();
         assert_eq!(配方.方家, "rime");
         assert_eq!(配方.名字, "rime-luna-pinyin");
         assert_eq!(配方.版本, None);
@@ -74,7 +543,7 @@ mod tests {
 
     #[test]
     fn 測試配方名片_規範的名字() {
-        let 配方 = 配方名片::from("rime-luna-pinyin");
+        let 配方 = 配方名片::from_str("rime-luna-pinyin").unwrap();
         assert_eq!(配方.方家, "rime");
         assert_eq!(配方.名字, "rime-luna-pinyin");
         assert_eq!(配方.版本, None);
@@ -82,9 +551,341 @@ mod tests {
 
     #[test]
     fn 測試配方名片_只有名字和版本() {
-        let 配方 = 配方名片::from("bopomofo@master");
+        let 配方 = 配方名片::from_str("bopomofo@master").unwrap();
         assert_eq!(配方.方家, "rime");
         assert_eq!(配方.名字, "rime-bopomofo");
         assert_eq!(配方.版本, Some("master".to_owned()));
     }
+
+    #[test]
+    fn 測試配方名片_指定配方() {
+        let 配方 = 配方名片::from_str("lotem/rime-octagram-data:hant").unwrap();
+        assert_eq!(配方.方家, "lotem");
+        assert_eq!(配方.名字, "rime-octagram-data");
+        assert_eq!(配方.版本, None);
+        assert_eq!(配方.配方名, Some("hant".to_owned()));
+        assert!(配方.配方參數.is_empty());
+        assert_eq!(配方.to_string(), "lotem/rime-octagram-data:hant");
+    }
+
+    #[test]
+    fn 測試配方名片_配方參數() {
+        let 配方 = 配方名片::from_str("emoji@master:customize:schema=luna_pinyin,x=1").unwrap();
+        assert_eq!(配方.方家, "rime");
+        assert_eq!(配方.名字, "rime-emoji");
+        assert_eq!(配方.版本, Some("master".to_owned()));
+        assert_eq!(配方.配方名, Some("customize".to_owned()));
+        assert_eq!(
+            配方.配方參數,
+            BTreeMap::from([
+                ("schema".to_owned(), "luna_pinyin".to_owned()),
+                ("x".to_owned(), "1".to_owned()),
+            ])
+        );
+        assert_eq!(
+            配方.to_string(),
+            "rime/rime-emoji@master:customize:schema=luna_pinyin,x=1"
+        );
+        assert_eq!(配方名片::from_str(&配方.to_string()).unwrap(), 配方);
+    }
+
+    #[test]
+    fn 測試配方名片_格式錯誤() {
+        use 配方名片錯誤::*;
+        let 錯誤 = |source| 配方名片::from_str(source).unwrap_err();
+        assert_eq!(錯誤(""), 空白);
+        assert_eq!(錯誤("  "), 空白);
+        assert_eq!(錯誤("@master"), 缺少("名字"));
+        assert_eq!(錯誤("lotem/"), 缺少("名字"));
+        assert_eq!(錯誤("luna-pinyin@"), 缺少("版本"));
+        assert_eq!(錯誤("emoji:"), 缺少("配方名"));
+        assert_eq!(錯誤("a/b/c"), 多餘的斜線("a/b/c".to_owned()));
+        assert_eq!(
+            錯誤("lotem/rime zhengma"),
+            非法字符 {
+                部分: "名字",
+                字符: ' '
+            }
+        );
+        assert_eq!(
+            錯誤("luna-pinyin@mas~ter"),
+            非法字符 {
+                部分: "版本",
+                字符: '~'
+            }
+        );
+        let 點開頭 = |部分, 名字: &str| 點開頭 {
+            部分,
+            名字: 名字.to_owned(),
+        };
+        assert_eq!(錯誤("rime/.."), 點開頭("名字", ".."));
+        assert_eq!(錯誤("rime/."), 點開頭("名字", "."));
+        assert_eq!(錯誤("rime/.hidden"), 點開頭("名字", ".hidden"));
+        assert_eq!(錯誤("github.com:rime/.."), 點開頭("名字", ".."));
+        assert_eq!(錯誤("https://example.com/team/.."), 點開頭("名字", ".."));
+        assert_eq!(錯誤("git@example.com:team/.git"), 缺少("名字"));
+        assert_eq!(
+            錯誤("git@example.com:team/.hidden.git"),
+            點開頭("名字", ".hidden")
+        );
+        assert_eq!(
+            錯誤("emoji:customize:schema"),
+            無效參數("schema".to_owned())
+        );
+        assert_eq!(
+            錯誤("emoji:customize:=luna_pinyin"),
+            無效參數("=luna_pinyin".to_owned())
+        );
+    }
+
+    #[test]
+    fn 測試配方名片_倉庫域名() {
+        let 配方 = 配方名片::from_str("gitee.com:team/rime-foo@dev:custom").unwrap();
+        assert_eq!(配方.倉庫域名, Some("gitee.com".to_owned()));
+        assert_eq!(配方.方家, "team");
+        assert_eq!(配方.名字, "rime-foo");
+        assert_eq!(配方.版本, Some("dev".to_owned()));
+        assert_eq!(配方.配方名, Some("custom".to_owned()));
+        assert_eq!(配方.來源, None);
+        assert_eq!(配方.to_string(), "gitee.com:team/rime-foo@dev:custom");
+
+        // 不是域名
+        let 配方 = 配方名片::from_str("lotem/rime-octagram-data:hant").unwrap();
+        assert_eq!(配方.倉庫域名, None);
+        let 配方 = 配方名片::from_str("rime.foo:bar").unwrap();
+        assert_eq!(配方.倉庫域名, None);
+        assert_eq!(配方.名字, "rime-rime.foo");
+        assert_eq!(配方.配方名, Some("bar".to_owned()));
+    }
+
+    #[test]
+    fn 測試配方名片_網址() {
+        let 配方 = 配方名片::from_str("https://git.example.com/team/rime-foo.git@dev").unwrap();
+        assert_eq!(配方.方家, "team");
+        assert_eq!(配方.名字, "rime-foo");
+        assert_eq!(配方.版本, Some("dev".to_owned()));
+        assert_eq!(
+            配方.來源,
+            Some(倉庫來源::網址(
+                "https://git.example.com/team/rime-foo.git".to_owned()
+            ))
+        );
+        assert_eq!(
+            配方.to_string(),
+            "https://git.example.com/team/rime-foo.git@dev"
+        );
+
+        let 配方 =
+            配方名片::from_str("ssh://git@git.example.com:2222/team/rime-foo:custom:x=1").unwrap();
+        assert_eq!(配方.方家, "team");
+        assert_eq!(配方.名字, "rime-foo");
+        assert_eq!(配方.版本, None);
+        assert_eq!(配方.配方名, Some("custom".to_owned()));
+        assert_eq!(
+            配方.來源,
+            Some(倉庫來源::網址(
+                "ssh://git@git.example.com:2222/team/rime-foo".to_owned()
+            ))
+        );
+
+        let 配方 = 配方名片::from_str("file:///srv/recipes/rime-foo").unwrap();
+        assert_eq!(配方.方家, "recipes");
+        assert_eq!(配方.名字, "rime-foo");
+
+        // `.` 開頭的目錄不作方家
+        let 配方 = 配方名片::from_str("file:///tmp/.tmpXYZ/rime-foo").unwrap();
+        assert_eq!(配方.方家, "local");
+        assert_eq!(配方.名字, "rime-foo");
+    }
+
+    #[test]
+    fn 測試配方名片_scp_式網址() {
+        let 配方 = 配方名片::from_str("git@git.example.com:team/rime-foo.git@v1.0:hant").unwrap();
+        assert_eq!(配方.方家, "team");
+        assert_eq!(配方.名字, "rime-foo");
+        assert_eq!(配方.版本, Some("v1.0".to_owned()));
+        assert_eq!(配方.配方名, Some("hant".to_owned()));
+        assert_eq!(
+            配方.來源,
+            Some(倉庫來源::網址(
+                "git@git.example.com:team/rime-foo.git".to_owned()
+            ))
+        );
+        assert_eq!(
+            配方.to_string(),
+            "git@git.example.com:team/rime-foo.git@v1.0:hant"
+        );
+    }
+
+    #[test]
+    fn 測試配方名片_本地目錄() {
+        let 配方 = 配方名片::from_str("./my-recipe").unwrap();
+        assert_eq!(配方.方家, "local");
+        assert_eq!(配方.名字, "my-recipe");
+        assert_eq!(配方.版本, None);
+        assert_eq!(
+            配方.來源,
+            Some(倉庫來源::本地(PathBuf::from("./my-recipe")))
+        );
+
+        let 配方 = 配方名片::from_str("/home/me/rime-foo/:custom:schema=foo").unwrap();
+        assert_eq!(配方.方家, "me");
+        assert_eq!(配方.名字, "rime-foo");
+        assert_eq!(配方.配方名, Some("custom".to_owned()));
+        assert_eq!(
+            配方.來源,
+            Some(倉庫來源::本地(PathBuf::from("/home/me/rime-foo/")))
+        );
+
+        let 配方 = 配方名片::from_str("/rime-cli").unwrap();
+        assert_eq!(配方.名字, "rime-cli");
+        assert_eq!(配方.來源, Some(倉庫來源::本地(PathBuf::from("/rime-cli"))));
+
+        assert_eq!(
+            配方名片::from_str("./").unwrap_err(),
+            配方名片錯誤::缺少("名字")
+        );
+    }
+
+    #[test]
+    fn 測試改寫版本() {
+        assert_eq!(改寫版本("luna-pinyin", Some("1.0")), "luna-pinyin@1.0");
+        assert_eq!(
+            改寫版本("emoji@master:customize:schema=luna_pinyin", Some("v2")),
+            "emoji@v2:customize:schema=luna_pinyin"
+        );
+        assert_eq!(
+            改寫版本("gitee.com:team/rime-foo@dev", None),
+            "gitee.com:team/rime-foo"
+        );
+        assert_eq!(
+            改寫版本("git@github.com:rime/rime-prelude.git:custom", Some("1.0")),
+            "git@github.com:rime/rime-prelude.git@1.0:custom"
+        );
+        assert_eq!(
+            改寫版本("https://user@example.com:8080/rime/rime-foo@v1", Some("v2")),
+            "https://user@example.com:8080/rime/rime-foo@v2"
+        );
+        assert_eq!(
+            改寫版本("./rime-foo:custom", Some("1.0")),
+            "./rime-foo:custom"
+        );
+    }
+
+    #[test]
+    fn 測試代入參數() {
+        let 參數 = BTreeMap::from([("schema".to_owned(), "luna_pinyin".to_owned())]);
+        assert_eq!(
+            代入參數("${schema}.custom.yaml", &參數).unwrap(),
+            "luna_pinyin.custom.yaml"
+        );
+        assert_eq!(
+            代入參數("${dict:-essay}.txt ${schema:-x}", &參數).unwrap(),
+            "essay.txt luna_pinyin"
+        );
+        assert!(代入參數("${dict}.txt", &參數).is_err());
+        assert!(代入參數("${dict", &參數).is_err());
+    }
+
+    #[test]
+    fn 測試配方文件_參數() {
+        let 內容 = r#"
+recipe:
+  Rx: emoji/customize
+  args:
+    - schema
+    - page_size=5
+patch_files:
+  ${schema}.custom.yaml:
+    patch/menu/page_size: ${page_size}
+"#;
+        let 參數 = BTreeMap::from([("schema".to_owned(), "luna_pinyin".to_owned())]);
+        let 配方 = 配方文件::代入參數解析(內容, &參數).unwrap();
+        let 衆字典 = 配方.補丁文件["luna_pinyin.custom.yaml"].衆字典();
+        assert_eq!(衆字典[0]["patch/menu/page_size"], Value::from(5));
+
+        assert!(配方文件::代入參數解析(內容, &BTreeMap::new()).is_err());
+    }
+
+    #[test]
+    fn 測試配方文件() {
+        let 配方 = 配方文件::解析(
+            r#"
+recipe:
+  Rx: emoji/customize
+  args:
+    - schema=luna_pinyin
+  description: >-
+    emoji for luna_pinyin
+install_files: >-
+  *.yaml
+  opencc/*.*
+patch_files:
+  luna_pinyin.custom.yaml:
+    - patch/switches/@next:
+        name: emoji_suggestion
+    - patch/engine/filters/@before 0: simplifier@emoji_suggestion
+  default.custom.yaml:
+    patch/menu/page_size: 9
+"#,
+        )
+        .unwrap();
+        assert_eq!(配方.說明.配方, "emoji/customize");
+        assert_eq!(配方.說明.args, ["schema=luna_pinyin"]);
+        assert_eq!(
+            配方.說明.description.as_deref(),
+            Some("emoji for luna_pinyin")
+        );
+        assert_eq!(配方.安裝文件.unwrap().衆模式(), ["*.yaml", "opencc/*.*"]);
+        assert_eq!(配方.補丁文件.len(), 2);
+        let 衆字典 = 配方.補丁文件["default.custom.yaml"].衆字典();
+        assert_eq!(衆字典.len(), 1);
+        assert_eq!(衆字典[0]["patch/menu/page_size"], Value::from(9));
+        let 衆紐 = 配方.補丁文件["luna_pinyin.custom.yaml"]
+            .衆字典()
+            .iter()
+            .flat_map(Mapping::keys)
+            .map(|紐| 紐.as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            衆紐,
+            ["patch/switches/@next", "patch/engine/filters/@before 0"]
+        );
+    }
+
+    #[test]
+    fn 測試配方文件_最簡() {
+        let 配方 = 配方文件::解析("recipe:\n  Rx: ohmyrime\n").unwrap();
+        assert_eq!(配方.說明.配方, "ohmyrime");
+        assert!(配方.說明.args.is_empty());
+        assert!(配方.安裝文件.is_none());
+        assert!(配方.補丁文件.is_empty());
+        assert!(配方.依賴.is_empty());
+    }
+
+    #[test]
+    fn 測試配方文件_依賴() {
+        let 配方 = 配方文件::解析(
+            r#"
+recipe:
+  Rx: terra-pinyin
+dependencies:
+  - stroke
+  - prelude@master
+"#,
+        )
+        .unwrap();
+        let 衆依賴 = 配方
+            .衆依賴()
+            .unwrap()
+            .iter()
+            .map(配方名片::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(衆依賴, ["rime/rime-stroke", "rime/rime-prelude@master"]);
+
+        let 配方 = 配方文件::解析("recipe: {Rx: x}\ndependencies: [a/b/c]\n").unwrap();
+        assert!(配方.衆依賴().is_err());
+        let 配方 = 配方文件::解析("recipe: {Rx: x}\ndependencies: [rime/..]\n").unwrap();
+        assert!(配方.衆依賴().is_err());
+    }
 }
@@ -3,18 +3,56 @@ use rime::{
     rime_api_call, rime_module_call, rime_struct_new, RimeConfig, RimeLeversApi, RimeTraits,
 };
 use std::ffi::{CStr, CString};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+
+/// 引擎用到的數據目錄. 共享數據可以是只讀的系統目錄, 用戶數據即工作場地.
+#[derive(Clone, Debug)]
+pub struct 引擎目錄 {
+    pub 共享數據: PathBuf,
+    pub 用戶數據: PathBuf,
+    /// 缺省爲共享數據目錄下的 build
+    pub 預構建數據: Option<PathBuf>,
+    /// 缺省爲用戶數據目錄下的 build
+    pub 整備區: Option<PathBuf>,
+}
+
+impl 引擎目錄 {
+    /// 共享數據和用戶數據都在工作場地.
+    pub fn 工作場地(路徑: &Path) -> Self {
+        引擎目錄 {
+            共享數據: 路徑.to_path_buf(),
+            用戶數據: 路徑.to_path_buf(),
+            預構建數據: None,
+            整備區: None,
+        }
+    }
+}
 
-pub fn 設置引擎啓動參數(工作場地: &PathBuf) -> anyhow::Result<()> {
-    log::debug!("設置引擎啓動參數. 工作場地: {}", 工作場地.display());
-    std::fs::create_dir_all(工作場地)?;
-    let 場地〇 = CString::new(工作場地.to_str().ok_or(anyhow!("路徑編碼轉換錯誤"))?)?;
+fn 路徑字符串(路徑: &Path) -> anyhow::Result<CString> {
+    Ok(CString::new(
+        路徑.to_str().ok_or(anyhow!("路徑編碼轉換錯誤"))?,
+    )?)
+}
+
+pub fn 設置引擎啓動參數(目錄: &引擎目錄) -> anyhow::Result<()> {
+    log::debug!("設置引擎啓動參數. 目錄: {:?}", 目錄);
+    std::fs::create_dir_all(&目錄.用戶數據)?;
+    let 共享數據〇 = 路徑字符串(&目錄.共享數據)?;
+    let 用戶數據〇 = 路徑字符串(&目錄.用戶數據)?;
+    let 預構建數據〇 = 目錄.預構建數據.as_deref().map(路徑字符串).transpose()?;
+    let 整備區〇 = 目錄.整備區.as_deref().map(路徑字符串).transpose()?;
     let 品名〇 = CString::new(env!("CARGO_PKG_NAME"))?;
     let 版本〇 = CString::new(env!("CARGO_PKG_VERSION"))?;
     let mut 啓動參數: RimeTraits = rime_struct_new!();
     啓動參數.data_size = std::mem::size_of::<RimeTraits>() as std::ffi::c_int;
-    啓動參數.shared_data_dir = 場地〇.as_ptr();
-    啓動參數.user_data_dir = 場地〇.as_ptr();
+    啓動參數.shared_data_dir = 共享數據〇.as_ptr();
+    啓動參數.user_data_dir = 用戶數據〇.as_ptr();
+    if let Some(預構建數據〇) = &預構建數據〇 {
+        啓動參數.prebuilt_data_dir = 預構建數據〇.as_ptr();
+    }
+    if let Some(整備區〇) = &整備區〇 {
+        啓動參數.staging_dir = 整備區〇.as_ptr();
+    }
     啓動參數.distribution_name = 品名〇.as_ptr();
     啓動參數.distribution_code_name = 品名〇.as_ptr();
     啓動參數.distribution_version = 版本〇.as_ptr();
@@ -88,16 +126,7 @@ pub fn 加入輸入方案列表(衆輸入方案: &[String]) -> anyhow::Result<()
         默認配置的自定義〇.as_ptr(),
         &mut 自定義配置
     );
-    let mut 既有方案 = vec![];
-    let 方案列表〇 = CString::new("patch/schema_list")?;
-    let 既有方案數 = rime_api_call!(config_list_size, &mut 自定義配置, 方案列表〇.as_ptr()) as u64;
-    for i in 0..既有方案數 {
-        let 列表項〇 = CString::new(format!("patch/schema_list/@{}/schema", i))?;
-        let 方案 = rime_api_call!(config_get_cstring, &mut 自定義配置, 列表項〇.as_ptr());
-        if !方案.is_null() {
-            既有方案.push(unsafe { CStr::from_ptr(方案) }.to_str()?.to_owned());
-        }
-    }
+    let 既有方案 = 讀取方案列表(&mut 自定義配置)?;
     let 新增方案 = 衆輸入方案.iter().filter(|方案| !既有方案.contains(方案));
     let 新增列表項〇 = CString::new("patch/schema_list/@next/schema")?;
     for 方案 in 新增方案 {
@@ -115,6 +144,79 @@ pub fn 加入輸入方案列表(衆輸入方案: &[String]) -> anyhow::Result<()
     Ok(())
 }
 
+/// 從 default.custom.yaml 的 `patch/schema_list` 中移除輸入方案, 返回實際移除了的.
+pub fn 移出輸入方案列表(衆輸入方案: &[String]) -> anyhow::Result<Vec<String>> {
+    log::debug!("移出輸入方案列表: {衆輸入方案:?}");
+    rime_api_call!(deployer_initialize, std::ptr::null_mut());
+
+    let mut 自定義配置: RimeConfig = rime_struct_new!();
+    let 默認配置的自定義〇 = CString::new("default.custom")?;
+    rime_api_call!(
+        user_config_open,
+        默認配置的自定義〇.as_ptr(),
+        &mut 自定義配置
+    );
+    let 方案列表〇 = CString::new("patch/schema_list")?;
+    let 既有項數 = rime_api_call!(config_list_size, &mut 自定義配置, 方案列表〇.as_ptr()) as u64;
+    let mut 移除方案 = vec![];
+    let mut 保留項 = vec![];
+    for i in 0..既有項數 {
+        let 列表項〇 = CString::new(format!("patch/schema_list/@{}", i))?;
+        let 方案〇 = CString::new(format!("patch/schema_list/@{}/schema", i))?;
+        let 方案 = rime_api_call!(config_get_cstring, &mut 自定義配置, 方案〇.as_ptr());
+        if !方案.is_null() {
+            let 方案 = unsafe { CStr::from_ptr(方案) }.to_str()?.to_owned();
+            if 衆輸入方案.contains(&方案) {
+                移除方案.push(方案);
+                continue;
+            }
+        }
+        // 整個列表項原樣保留, 不只是其中的 schema
+        let mut 列表項: RimeConfig = rime_struct_new!();
+        rime_api_call!(
+            config_get_item,
+            &mut 自定義配置,
+            列表項〇.as_ptr(),
+            &mut 列表項
+        );
+        保留項.push(列表項);
+    }
+    if !移除方案.is_empty() {
+        // 重建列表, 放回其餘的列表項
+        rime_api_call!(config_create_list, &mut 自定義配置, 方案列表〇.as_ptr());
+        let 新增列表項〇 = CString::new("patch/schema_list/@next")?;
+        for 列表項 in 保留項.iter_mut() {
+            rime_api_call!(
+                config_set_item,
+                &mut 自定義配置,
+                新增列表項〇.as_ptr(),
+                列表項
+            );
+        }
+    }
+    for mut 列表項 in 保留項 {
+        rime_api_call!(config_close, &mut 列表項);
+    }
+    rime_api_call!(config_close, &mut 自定義配置);
+
+    rime_api_call!(finalize);
+    Ok(移除方案)
+}
+
+fn 讀取方案列表(自定義配置: &mut RimeConfig) -> anyhow::Result<Vec<String>> {
+    let mut 既有方案 = vec![];
+    let 方案列表〇 = CString::new("patch/schema_list")?;
+    let 既有方案數 = rime_api_call!(config_list_size, 自定義配置, 方案列表〇.as_ptr()) as u64;
+    for i in 0..既有方案數 {
+        let 列表項〇 = CString::new(format!("patch/schema_list/@{}/schema", i))?;
+        let 方案 = rime_api_call!(config_get_cstring, 自定義配置, 列表項〇.as_ptr());
+        if !方案.is_null() {
+            既有方案.push(unsafe { CStr::from_ptr(方案) }.to_str()?.to_owned());
+        }
+    }
+    Ok(既有方案)
+}
+
 pub fn 選擇輸入方案(方案: &str) -> anyhow::Result<()> {
     log::debug!("選擇輸入方案: {方案}");
     rime_api_call!(deployer_initialize, std::ptr::null_mut());
@@ -160,7 +262,7 @@ mod tests {
                 assert_ok!(std::fs::remove_dir_all(&*公共測試場地));
             }
         });
-        assert_ok!(設置引擎啓動參數(&公共測試場地));
+        assert_ok!(設置引擎啓動參數(&引擎目錄::工作場地(&公共測試場地)));
     }
 
     #[test]
@@ -245,7 +347,7 @@ patch:
         if 專用測試場地.exists() {
             assert_ok!(std::fs::remove_dir_all(&專用測試場地));
         }
-        assert_ok!(設置引擎啓動參數(&專用測試場地));
+        assert_ok!(設置引擎啓動參數(&引擎目錄::工作場地(&專用測試場地)));
         assert_ok!(write(
             專用測試場地.join("default.yaml"),
             r#"
@@ -289,7 +391,7 @@ schema:
         if 專用測試場地.exists() {
             assert_ok!(std::fs::remove_dir_all(&專用測試場地));
         }
-        assert_ok!(設置引擎啓動參數(&專用測試場地));
+        assert_ok!(設置引擎啓動參數(&引擎目錄::工作場地(&專用測試場地)));
 
         let 新增輸入方案 = vec!["protoss".to_owned(), "terran".to_owned()];
         assert_ok!(加入輸入方案列表(&新增輸入方案));
@@ -316,6 +418,61 @@ schema:
         ));
     }
 
+    #[test]
+    fn 測試移出輸入方案列表() {
+        let _佔 = 佔用引擎機位.write().unwrap();
+        let 專用測試場地 = std::env::temp_dir().join("rime_levers_tests_remove");
+        if 專用測試場地.exists() {
+            assert_ok!(std::fs::remove_dir_all(&專用測試場地));
+        }
+        assert_ok!(設置引擎啓動參數(&引擎目錄::工作場地(&專用測試場地)));
+
+        let 衆輸入方案 = vec!["protoss".to_owned(), "terran".to_owned(), "zerg".to_owned()];
+        assert_ok!(加入輸入方案列表(&衆輸入方案));
+
+        let 移除方案 = assert_ok!(移出輸入方案列表(&[
+            "terran".to_owned(),
+            "xel_naga".to_owned()
+        ]));
+        assert_eq!(移除方案, ["terran"]);
+        let 自定義配置 = 專用測試場地.join("default.custom.yaml");
+        let 自定義配置內容 = assert_ok!(read_to_string(&自定義配置));
+        assert!(自定義配置內容.contains(
+            r#"patch:
+  schema_list:
+    - {schema: protoss}
+    - {schema: zerg}"#
+        ));
+    }
+
+    #[test]
+    fn 測試移出輸入方案列表_保留列表項的其他設定() {
+        let _佔 = 佔用引擎機位.write().unwrap();
+        let 專用測試場地 = std::env::temp_dir().join("rime_levers_tests_remove_items");
+        if 專用測試場地.exists() {
+            assert_ok!(std::fs::remove_dir_all(&專用測試場地));
+        }
+        assert_ok!(設置引擎啓動參數(&引擎目錄::工作場地(&專用測試場地)));
+        let 自定義配置 = 專用測試場地.join("default.custom.yaml");
+        assert_ok!(std::fs::write(
+            &自定義配置,
+            r#"patch:
+  schema_list:
+    - {schema: protoss}
+    - {schema: terran}
+    - {schema: zerg, case: [mode/zerg]}
+"#
+        ));
+
+        let 移除方案 = assert_ok!(移出輸入方案列表(&["protoss".to_owned()]));
+        assert_eq!(移除方案, ["protoss"]);
+        let 自定義配置內容 = assert_ok!(read_to_string(&自定義配置));
+        assert!(!自定義配置內容.contains("protoss"));
+        assert!(自定義配置內容.contains("schema: terran"));
+        assert!(自定義配置內容.contains("schema: zerg"));
+        assert!(自定義配置內容.contains("mode/zerg"));
+    }
+
     #[test]
     fn 測試選擇輸入方案() {
         let _佔 = 佔用引擎機位.write().unwrap();
@@ -323,7 +480,7 @@ schema:
         if 專用測試場地.exists() {
             assert_ok!(std::fs::remove_dir_all(&專用測試場地));
         }
-        assert_ok!(設置引擎啓動參數(&專用測試場地));
+        assert_ok!(設置引擎啓動參數(&引擎目錄::工作場地(&專用測試場地)));
 
         let grrrr_之選 = "protoss";
         assert_ok!(選擇輸入方案(grrrr_之選));
@@ -0,0 +1,340 @@
+use crate::recipe::規範的配方名字;
+
+use anyhow::{anyhow, bail};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct 新建參數 {
+    /// 輸入方案模板: pinyin (拼音), table (碼表, 形碼)
+    #[structopt(short, long, default_value = "pinyin")]
+    template: 方案模板,
+    /// 輸入方案的顯示名稱, 缺省同方案 ID
+    #[structopt(long)]
+    schema_name: Option<String>,
+    /// 作者
+    #[structopt(long)]
+    author: Option<String>,
+    /// 用於反查的輸入方案 ID, 如 luna_pinyin
+    #[structopt(long)]
+    reverse_lookup: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum 方案模板 {
+    拼音,
+    碼表,
+}
+
+impl FromStr for 方案模板 {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pinyin" => Ok(方案模板::拼音),
+            "table" | "shape" => Ok(方案模板::碼表),
+            _ => bail!("未知的方案模板: {s}. 可選 pinyin, table"),
+        }
+    }
+}
+
+/// 在上級目錄中新建配方目錄 `rime-<名字>`, 返回其路徑.
+pub fn 新建配方(
+    名字: &str, 參數: &新建參數, 上級目錄: &Path
+) -> anyhow::Result<PathBuf> {
+    let 方案 = 方案標識(名字)?;
+    let 配方名字 = 規範的配方名字(名字);
+    let 配方目錄 = 上級目錄.join(&配方名字);
+    if 配方目錄.exists() {
+        bail!("目錄已存在: {}", 配方目錄.display());
+    }
+    fs::create_dir_all(&配方目錄)?;
+
+    let 名稱 = 參數.schema_name.as_deref().unwrap_or(&方案);
+    let 作者 = 參數.author.as_deref().unwrap_or("佚名");
+    for (文件, 內容) in [
+        ("recipe.yaml".to_owned(), 配方文件(&配方名字, &方案, 名稱)),
+        (
+            format!("{方案}.schema.yaml"),
+            方案文件(&方案, 名稱, 作者, 參數),
+        ),
+        (format!("{方案}.dict.yaml"), 詞典文件(&方案, 參數.template)),
+        ("README.md".to_owned(), 說明文件(&配方名字, 名稱)),
+    ] {
+        fs::write(配方目錄.join(&文件), 內容)?;
+        log::debug!("新建文件: {文件}");
+    }
+    初始化倉庫(&配方目錄)?;
+    log::info!("新建好了配方 {}", 配方目錄.display());
+    Ok(配方目錄)
+}
+
+/// 由配方名字得出輸入方案 ID: 去掉 `rime-` 前綴, 連字符換成下劃線.
+fn 方案標識(名字: &str) -> anyhow::Result<String> {
+    let 方案 = 名字.strip_prefix("rime-").unwrap_or(名字).replace('-', "_");
+    let 合法 = 方案.starts_with(|c: char| c.is_ascii_alphabetic())
+        && 方案.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !合法 {
+        bail!("配方名字應以字母開頭, 只含字母, 數字, - 和 _: {名字}");
+    }
+    Ok(方案)
+}
+
+fn 配方文件(配方名字: &str, 方案: &str, 名稱: &str) -> String {
+    format!(
+        r#"# encoding: utf-8
+---
+recipe:
+  Rx: {配方名字}
+  args:
+  description: >-
+    {名稱}輸入方案
+
+install_files: >-
+  {方案}.schema.yaml
+  {方案}.dict.yaml
+"#
+    )
+}
+
+fn 方案文件(方案: &str, 名稱: &str, 作者: &str, 參數: &新建參數) -> String {
+    let (翻譯器, 拼寫設定, 翻譯設定) = match 參數.template {
+        方案模板::拼音 => (
+            "script_translator",
+            r#"  alphabet: zyxwvutsrqponmlkjihgfedcba
+  delimiter: " '"
+  algebra:
+    - abbrev/^([a-z]).+$/$1/"#,
+            "  spelling_hints: 5",
+        ),
+        方案模板::碼表 => (
+            "table_translator",
+            r#"  alphabet: zyxwvutsrqponmlkjihgfedcba
+  max_code_length: 4
+  auto_select: true"#,
+            "  enable_charset_filter: true
+  enable_sentence: false
+  enable_user_dict: false",
+        ),
+    };
+    let mut 依賴 = String::new();
+    let mut 反查翻譯器 = String::new();
+    let mut 反查設定 = String::new();
+    if let Some(反查方案) = &參數.reverse_lookup {
+        依賴 = format!("  dependencies:\n    - {反查方案}\n");
+        反查翻譯器 = "    - reverse_lookup_translator\n".to_owned();
+        反查設定 = format!(
+            r#"
+reverse_lookup:
+  dictionary: {反查方案}
+  prefix: "`"
+  suffix: "'"
+  tips: 〔反查〕
+"#
+        );
+    }
+    let 反查模式 = if 參數.reverse_lookup.is_some() {
+        "\n  patterns:\n    reverse_lookup: \"`[a-z]*'?$\""
+    } else {
+        ""
+    };
+    format!(
+        r#"# Rime schema
+# encoding: utf-8
+
+schema:
+  schema_id: {方案}
+  name: {名稱}
+  version: "0.1"
+  author:
+    - {作者}
+  description: |
+    {名稱}輸入方案
+{依賴}
+switches:
+  - name: ascii_mode
+    reset: 0
+    states: [ 中文, 西文 ]
+  - name: full_shape
+    states: [ 半角, 全角 ]
+
+engine:
+  processors:
+    - ascii_composer
+    - recognizer
+    - key_binder
+    - speller
+    - punctuator
+    - selector
+    - navigator
+    - express_editor
+  segmentors:
+    - ascii_segmentor
+    - matcher
+    - abc_segmentor
+    - punct_segmentor
+    - fallback_segmentor
+  translators:
+    - punct_translator
+    - {翻譯器}
+{反查翻譯器}
+speller:
+{拼寫設定}
+
+translator:
+  dictionary: {方案}
+{翻譯設定}
+{反查設定}
+punctuator:
+  import_preset: default
+
+key_binder:
+  import_preset: default
+
+recognizer:
+  import_preset: default{反查模式}
+"#
+    )
+}
+
+fn 詞典文件(方案: &str, 模板: 方案模板) -> String {
+    let 示例 = match 模板 {
+        方案模板::拼音 => "你\tni\n好\thao\n",
+        方案模板::碼表 => "一\ta\n二\tb\n",
+    };
+    format!(
+        r#"# Rime dictionary
+# encoding: utf-8
+
+---
+name: {方案}
+version: "0.1"
+sort: by_weight
+...
+
+# 字詞	編碼	權重
+{示例}"#
+    )
+}
+
+fn 說明文件(配方名字: &str, 名稱: &str) -> String {
+    format!(
+        r#"# {配方名字}
+
+{名稱}輸入方案.
+
+## 安裝
+
+```
+rime install <方家>/{配方名字}
+```
+"#
+    )
+}
+
+/// 初始化 git 倉庫並暫存全部文件. 設置了 git 用戶時, 順便作首次提交.
+fn 初始化倉庫(配方目錄: &Path) -> anyhow::Result<()> {
+    let 倉庫 = git2::Repository::init(配方目錄)?;
+    let mut 索引 = 倉庫.index()?;
+    索引.add_all(["*"], git2::IndexAddOption::DEFAULT, None)?;
+    索引.write()?;
+    let 簽名 = match 倉庫.signature() {
+        Ok(簽名) => 簽名,
+        Err(_) => {
+            log::warn!("未設置 git 用戶, 沒有作首次提交");
+            return Ok(());
+        }
+    };
+    let 樹 = 倉庫.find_tree(索引.write_tree()?)?;
+    倉庫
+        .commit(Some("HEAD"), &簽名, &簽名, "新建配方", &樹, &[])
+        .map_err(|e| anyhow!("首次提交失敗: {e}"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::recipe::配方文件;
+    use claims::assert_ok;
+    use std::collections::BTreeMap;
+
+    fn 參數(template: 方案模板, reverse_lookup: Option<&str>) -> 新建參數 {
+        新建參數 {
+            template,
+            schema_name: Some("我的方案".to_owned()),
+            author: None,
+            reverse_lookup: reverse_lookup.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn 測試方案標識() {
+        assert_eq!(assert_ok!(方案標識("my-schema")), "my_schema");
+        assert_eq!(assert_ok!(方案標識("rime-wubi86")), "wubi86");
+        assert!(方案標識("9key").is_err());
+        assert!(方案標識("foo/bar").is_err());
+    }
+
+    #[test]
+    fn 測試新建配方() {
+        let tmp_dir = assert_ok!(tempfile::tempdir());
+        let 配方目錄 = assert_ok!(新建配方(
+            "my-schema",
+            &參數(方案模板::拼音, None),
+            tmp_dir.path()
+        ));
+        assert_eq!(配方目錄, tmp_dir.path().join("rime-my-schema"));
+        assert!(配方目錄.join("README.md").exists());
+        assert!(配方目錄.join(".git").is_dir());
+
+        let 配方 = assert_ok!(配方文件::讀取(
+            &配方目錄.join("recipe.yaml"),
+            &BTreeMap::new()
+        ));
+        assert_eq!(配方.說明.配方, "rime-my-schema");
+        assert_eq!(
+            配方.安裝文件.unwrap().衆模式(),
+            ["my_schema.schema.yaml", "my_schema.dict.yaml"]
+        );
+
+        let 方案: serde_yaml::Value = assert_ok!(serde_yaml::from_str(&assert_ok!(
+            fs::read_to_string(配方目錄.join("my_schema.schema.yaml"))
+        )));
+        assert_eq!(方案["schema"]["schema_id"], "my_schema");
+        assert_eq!(方案["schema"]["name"], "我的方案");
+        assert_eq!(方案["translator"]["dictionary"], "my_schema");
+        assert_eq!(方案["engine"]["translators"][1], "script_translator");
+
+        assert!(新建配方("my-schema", &參數(方案模板::拼音, None), tmp_dir.path()).is_err());
+    }
+
+    #[test]
+    fn 測試新建配方_碼表反查() {
+        let tmp_dir = assert_ok!(tempfile::tempdir());
+        let 配方目錄 = assert_ok!(新建配方(
+            "wubi",
+            &參數(方案模板::碼表, Some("luna_pinyin")),
+            tmp_dir.path()
+        ));
+        let 方案: serde_yaml::Value = assert_ok!(serde_yaml::from_str(&assert_ok!(
+            fs::read_to_string(配方目錄.join("wubi.schema.yaml"))
+        )));
+        assert_eq!(方案["engine"]["translators"][1], "table_translator");
+        assert_eq!(
+            方案["engine"]["translators"][2],
+            "reverse_lookup_translator"
+        );
+        assert_eq!(方案["schema"]["dependencies"][0], "luna_pinyin");
+        assert_eq!(方案["reverse_lookup"]["dictionary"], "luna_pinyin");
+        assert_eq!(方案["speller"]["max_code_length"], 4);
+
+        let 詞典 = assert_ok!(fs::read_to_string(配方目錄.join("wubi.dict.yaml")));
+        let (頭部, _) = 詞典.split_once("\n...\n").unwrap();
+        let 頭部: serde_yaml::Value = assert_ok!(serde_yaml::from_str(頭部));
+        assert_eq!(頭部["name"], "wubi");
+    }
+}
@@ -0,0 +1,39 @@
+//! 各模塊的測試共用的小工具.
+
+use crate::recipe::配方名片;
+
+use std::path::Path;
+use std::str::FromStr;
+
+pub fn 配方(source: &str) -> 配方名片 {
+    配方名片::from_str(source).unwrap()
+}
+
+/// 在倉庫的當前分支上提交一個文件. 倉庫還沒有提交的, 這就是第一次提交.
+pub fn 提交文件(路徑: &Path, 文件: &str, 內容: &str) -> Result<git2::Oid, git2::Error> {
+    let 倉庫 = git2::Repository::open(路徑)?;
+    std::fs::write(路徑.join(文件), 內容).unwrap();
+    let mut 索引 = 倉庫.index()?;
+    索引.add_path(Path::new(文件))?;
+    索引.write()?;
+    let 樹 = 倉庫.find_tree(索引.write_tree()?)?;
+    let 簽名 = git2::Signature::now("rime", "rime@example.com")?;
+    let 父提交 = 倉庫
+        .head()
+        .ok()
+        .map(|head| head.peel_to_commit())
+        .transpose()?;
+    let 衆父提交 = 父提交.iter().collect::<Vec<_>>();
+    倉庫.commit(Some("HEAD"), &簽名, &簽名, 內容, &樹, &衆父提交)
+}
+
+/// 在本地造一個上游倉庫, 有兩次提交, 第一次提交打了標籤 v1.
+pub fn 造上游倉庫(路徑: &Path) -> Result<Vec<git2::Oid>, git2::Error> {
+    let 倉庫 = git2::Repository::init(路徑)?;
+    let 衆提交 = ["v1", "v2"]
+        .into_iter()
+        .map(|內容| 提交文件(路徑, "ohmyrime.schema.yaml", 內容))
+        .collect::<Result<Vec<_>, _>>()?;
+    倉庫.tag_lightweight("v1", &倉庫.find_object(衆提交[0], None)?, false)?;
+    Ok(衆提交)
+}
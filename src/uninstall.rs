@@ -0,0 +1,217 @@
+use crate::download::是該倉庫的檢出;
+use crate::installed::{安裝記錄, 安裝記錄文件名, 文件摘要, 配方安裝記錄};
+use crate::package::配方包;
+use crate::recipe::配方名片;
+use crate::rime_levers::{引擎目錄, 移出輸入方案列表, 設置引擎啓動參數};
+
+use anyhow::{anyhow, bail};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// 刪除配方安裝的文件, 並把其中的輸入方案移出方案列表.
+///
+/// 安裝後被改動過的文件不刪, 列出來提醒用戶.
+pub fn 卸載配方(
+    配方: &配方名片, 目錄: &引擎目錄, 刪除配方包: bool
+) -> anyhow::Result<()> {
+    log::debug!("卸載配方: {配方}");
+    let 工作場地 = &目錄.用戶數據;
+    let 記錄路徑 = 工作場地.join(安裝記錄文件名);
+    if !記錄路徑.exists() {
+        bail!("沒有安裝記錄: {}", 記錄路徑.display());
+    }
+    let mut 記錄 = 安裝記錄::讀取(&記錄路徑)?;
+    let 配方記錄 = 記錄
+        .移除(配方)
+        .ok_or(anyhow!("配方沒有安裝: {}", 配方.不論版本()))?;
+
+    let (要刪除, 保留) = 分揀安裝的文件(&記錄, &配方記錄, 工作場地)?;
+    for 文件 in &保留 {
+        println!("安裝後改動過, 未刪除: {}", 文件.display());
+    }
+
+    // 先改方案列表再刪文件: 改不成時文件和安裝記錄都原樣在, 可以重試
+    let 衆輸入方案 = 要刪除
+        .iter()
+        .filter_map(|文件| 文件.to_str()?.strip_suffix(".schema.yaml"))
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    if !衆輸入方案.is_empty() && 工作場地.join("default.custom.yaml").exists() {
+        設置引擎啓動參數(目錄)?;
+        for 方案 in 移出輸入方案列表(&衆輸入方案)? {
+            log::info!("移出輸入方案列表: {方案}");
+        }
+    }
+    刪除文件(&要刪除, 工作場地)?;
+    log::info!("刪除了 {} 個文件. {配方}", 要刪除.len());
+    記錄.寫入(&記錄路徑)?;
+
+    if 刪除配方包 {
+        let 包 = 配方包 {
+            配方: 配方.clone(),
+            倉庫域名: None,
+        };
+        let 本地倉庫 = 包.本地路徑(工作場地);
+        if 包.是本地目錄() {
+            log::warn!("不刪除本地配方目錄: {}", 本地倉庫.display());
+        } else if 記錄.有配方來自(配方)? {
+            log::warn!("配方包中還有別的配方安裝着, 不刪除: {}", 本地倉庫.display());
+        } else {
+            刪除配方包目錄(&包, 工作場地)?;
+        }
+    }
+    Ok(())
+}
+
+/// 刪除下載的配方包. 目錄不是從配方所在的倉庫檢出的, 則不動,
+/// 以免誤刪同名的別家倉庫或用戶自己放在那裏的文件.
+fn 刪除配方包目錄(包: &配方包, 工作場地: &Path) -> anyhow::Result<()> {
+    let 本地倉庫 = 包.本地路徑(工作場地);
+    if !本地倉庫.exists() {
+        return Ok(());
+    }
+    if !是該倉庫的檢出(包, &本地倉庫) {
+        log::warn!(
+            "配方包目錄不是從 {} 下載的, 不刪除: {}",
+            包.倉庫地址(),
+            本地倉庫.display()
+        );
+        return Ok(());
+    }
+    fs::remove_dir_all(&本地倉庫)?;
+    log::info!("刪除了配方包: {}", 本地倉庫.display());
+    // 方家名下沒有別的配方包了
+    if let Some(方家目錄) = 本地倉庫.parent() {
+        if fs::read_dir(方家目錄)?.next().is_none() {
+            fs::remove_dir(方家目錄)?;
+        }
+    }
+    Ok(())
+}
+
+/// 分揀配方記錄中的文件, 跳過別的配方也安裝了的, 以及已不存在的.
+/// 返回要刪除的文件和因用戶改動過而保留的文件.
+fn 分揀安裝的文件(
+    記錄: &安裝記錄,
+    配方記錄: &配方安裝記錄,
+    工作場地: &Path,
+) -> anyhow::Result<(Vec<PathBuf>, Vec<PathBuf>)> {
+    let mut 要刪除 = vec![];
+    let mut 保留 = vec![];
+    for (文件, 摘要) in &配方記錄.衆文件 {
+        if 記錄.有配方用到(文件) {
+            log::debug!("別的配方也安裝了, 不刪除: {文件}");
+            continue;
+        }
+        let 路徑 = 工作場地.join(文件);
+        if !路徑.exists() {
+            continue;
+        }
+        if &文件摘要(&路徑)? != 摘要 {
+            保留.push(PathBuf::from(文件));
+        } else {
+            要刪除.push(PathBuf::from(文件));
+        }
+    }
+    Ok((要刪除, 保留))
+}
+
+/// 刪除工作場地中的文件, 順便刪除空了的子目錄, 如 opencc/
+fn 刪除文件(衆文件: &[PathBuf], 工作場地: &Path) -> anyhow::Result<()> {
+    for 文件 in 衆文件 {
+        let 路徑 = 工作場地.join(文件);
+        fs::remove_file(&路徑)?;
+        log::debug!("刪除文件: {}", 文件.display());
+        if let Some(上級) = 路徑.parent() {
+            if 上級 != 工作場地 && fs::read_dir(上級)?.next().is_none() {
+                fs::remove_dir(上級)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::{提交文件, 配方};
+    use claims::assert_ok;
+    use std::fs::{create_dir_all, write};
+
+    #[test]
+    fn 測試刪除安裝的文件() {
+        let tmp_dir = assert_ok!(tempfile::tempdir());
+        let 工作場地 = tmp_dir.path();
+        assert_ok!(create_dir_all(工作場地.join("opencc")));
+        let 衆文件 = [
+            "foo.schema.yaml",
+            "foo.dict.yaml",
+            "common.txt",
+            "opencc/foo.json",
+        ]
+        .map(PathBuf::from);
+        for 文件 in &衆文件 {
+            assert_ok!(write(工作場地.join(文件), "安裝的內容"));
+        }
+        let mut 記錄 = 安裝記錄::default();
+        assert_ok!(記錄.記錄(&配方("foo"), 工作場地, &衆文件));
+        assert_ok!(記錄.記錄(&配方("bar"), 工作場地, &[PathBuf::from("common.txt")]));
+        assert_ok!(write(工作場地.join("foo.dict.yaml"), "用戶改動過"));
+
+        let 配方記錄 = 記錄.移除(&配方("foo")).unwrap();
+        let (要刪除, 保留) = assert_ok!(分揀安裝的文件(&記錄, &配方記錄, 工作場地));
+        assert_eq!(
+            要刪除,
+            ["foo.schema.yaml", "opencc/foo.json"].map(PathBuf::from)
+        );
+        assert_eq!(保留, [PathBuf::from("foo.dict.yaml")]);
+        // 分揀時不刪除
+        assert!(工作場地.join("foo.schema.yaml").exists());
+        assert_ok!(刪除文件(&要刪除, 工作場地));
+        assert!(工作場地.join("foo.dict.yaml").exists());
+        assert!(工作場地.join("common.txt").exists());
+        assert!(!工作場地.join("foo.schema.yaml").exists());
+        assert!(!工作場地.join("opencc").exists());
+    }
+
+    /// 建一個有提交的倉庫, 遠端指向 `網址`.
+    fn 造倉庫(路徑: &Path, 網址: &str) {
+        let 倉庫 = assert_ok!(git2::Repository::init(路徑));
+        assert_ok!(倉庫.remote("origin", 網址));
+        assert_ok!(提交文件(路徑, "README.md", "init"));
+    }
+
+    #[test]
+    fn 測試刪除配方包目錄() {
+        let tmp_dir = assert_ok!(tempfile::tempdir());
+        let 工作場地 = tmp_dir.path();
+        let 包 = |source| 配方包 {
+            配方: 配方(source),
+            倉庫域名: None,
+        };
+
+        // 不是檢出的目錄
+        let 包甲 = 包("foo");
+        let 目錄甲 = 包甲.本地路徑(工作場地);
+        assert_ok!(create_dir_all(&目錄甲));
+        assert_ok!(write(目錄甲.join("mine.yaml"), "用戶的文件"));
+        assert_ok!(刪除配方包目錄(&包甲, 工作場地));
+        assert!(目錄甲.join("mine.yaml").exists());
+
+        // 從別處下載的同名倉庫
+        let 包乙 = 包("bar");
+        let 目錄乙 = 包乙.本地路徑(工作場地);
+        造倉庫(&目錄乙, "https://gitee.com/rime/rime-bar.git");
+        assert_ok!(刪除配方包目錄(&包乙, 工作場地));
+        assert!(目錄乙.exists());
+
+        let 包丙 = 包("team/rime-baz");
+        let 目錄丙 = 包丙.本地路徑(工作場地);
+        造倉庫(&目錄丙, "git@github.com:team/rime-baz");
+        assert_ok!(刪除配方包目錄(&包丙, 工作場地));
+        assert!(!目錄丙.exists());
+        // 方家目錄空了, 一併刪除
+        assert!(!工作場地.join("pkg/team").exists());
+    }
+}
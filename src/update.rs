@@ -0,0 +1,374 @@
+use crate::download::{git, 同步參數, 拉取, 網絡參數, 跟蹤的分支, 跟蹤的遠端};
+use crate::install::安裝配方;
+use crate::installed::{安裝記錄, 安裝記錄文件名};
+use crate::lock::{鎖定文件, 鎖定文件名};
+use crate::package::配方包;
+use crate::recipe::配方名片;
+use crate::rime_levers::{引擎目錄, 製備輸入法固件, 設置引擎啓動參數};
+
+use anyhow::bail;
+use indicatif::ProgressBar;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+pub struct 更新參數 {
+    /// 重新安裝來自更新了的配方包的配方
+    #[structopt(long)]
+    install: bool,
+    /// 更新後重新構建輸入法固件
+    #[structopt(long)]
+    build: bool,
+    #[structopt(flatten)]
+    網絡參數: 網絡參數,
+    #[structopt(flatten)]
+    同步參數: 同步參數,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum 更新結果 {
+    /// 檢出的是標籤或提交, 不跟隨分支
+    固定版本(String),
+    已是最新(String),
+    已更新 {
+        舊提交: String,
+        新提交: String,
+        提交數: usize,
+    },
+}
+
+impl fmt::Display for 更新結果 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            更新結果::固定版本(版本) => write!(f, "固定在 {版本}, 跳過"),
+            更新結果::已是最新(分支) => write!(f, "{分支} 已是最新"),
+            更新結果::已更新 {
+                舊提交,
+                新提交,
+                提交數,
+            } => write!(
+                f,
+                "{} -> {} ({提交數} 個提交)",
+                短提交(舊提交),
+                短提交(新提交)
+            ),
+        }
+    }
+}
+
+/// 逐個更新 `pkg/` 下的配方包, 報告各自的變化.
+pub fn 更新配方包(目錄: &引擎目錄, 參數: &更新參數) -> anyhow::Result<()> {
+    let 工作場地 = &目錄.用戶數據;
+    參數.網絡參數.設置超時()?;
+    let mut 更新了的 = vec![];
+    let mut 失敗的 = vec![];
+    for 包 in 配方包::列出下載的配方包(工作場地)? {
+        let 本地倉庫 = 包.本地路徑(工作場地);
+        let 進度 = git::progress_bar(&包.配方.to_string());
+        let 結果 = 更新倉庫(
+            &本地倉庫,
+            &包.配方,
+            參數.同步參數.策略(),
+            &參數.網絡參數,
+            &進度,
+        );
+        進度.finish_and_clear();
+        match 結果 {
+            Ok(結果) => {
+                println!("{}: {}", 包.配方, 結果);
+                if let 更新結果::已更新 { 新提交, .. } = 結果 {
+                    更新了的.push((本地倉庫, 新提交));
+                }
+            }
+            Err(e) => {
+                println!("{}: 更新失敗: {e}", 包.配方);
+                失敗的.push(包.配方.to_string());
+            }
+        }
+    }
+
+    let 鎖定文件路徑 = 工作場地.join(鎖定文件名);
+    if !更新了的.is_empty() && 鎖定文件路徑.exists() {
+        let mut 鎖定 = 鎖定文件::讀取(&鎖定文件路徑)?;
+        for (本地倉庫, 新提交) in &更新了的 {
+            鎖定.更新提交(本地倉庫, 工作場地, 新提交)?;
+        }
+        鎖定.寫入(&鎖定文件路徑)?;
+    }
+
+    if 參數.install {
+        let 衆本地倉庫 = 更新了的
+            .into_iter()
+            .map(|(本地倉庫, _)| 本地倉庫)
+            .collect::<Vec<_>>();
+        for 配方 in 來自配方包的配方(工作場地, &衆本地倉庫)? {
+            安裝配方(&配方, 目錄)?;
+        }
+    }
+    if 參數.build {
+        設置引擎啓動參數(目錄)?;
+        製備輸入法固件()?;
+    }
+
+    if !失敗的.is_empty() {
+        bail!(
+            "有 {} 個配方包更新失敗: {}",
+            失敗的.len(),
+            失敗的.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// 按 HEAD 所在分支跟蹤的遠端分支更新; 沒有設置跟蹤的, 按遠端的默認分支.
+fn 更新倉庫(
+    本地倉庫: &Path,
+    配方: &配方名片,
+    策略: git::SyncPolicy,
+    網絡參數: &網絡參數,
+    進度: &ProgressBar,
+) -> anyhow::Result<更新結果> {
+    if git::head_branch(本地倉庫)?.is_none() {
+        return Ok(更新結果::固定版本(git::describe_head(本地倉庫)?));
+    }
+    let 網絡 = 網絡參數.網絡(&git::remote_url(本地倉庫, &跟蹤的遠端(本地倉庫)?)?, 配方)?;
+    let (遠端, 遠端分支) = 網絡參數.帶重試(進度, || 跟蹤的分支(本地倉庫, &網絡))?;
+    let 舊提交 = git::head_commit_id(本地倉庫)?;
+    網絡參數.帶重試(進度, || {
+        拉取(本地倉庫, &遠端, &遠端分支, 策略, None, 進度, &網絡)
+    })?;
+    let 新提交 = git::head_commit_id(本地倉庫)?;
+    if 新提交 == 舊提交 {
+        return Ok(更新結果::已是最新(遠端分支));
+    }
+    let 提交數 = git::count_commits(本地倉庫, &舊提交, &新提交)?;
+    Ok(更新結果::已更新 {
+        舊提交,
+        新提交,
+        提交數,
+    })
+}
+
+/// 安裝記錄中, 來自這些配方包目錄的配方.
+fn 來自配方包的配方(
+    工作場地: &Path,
+    衆本地倉庫: &[PathBuf],
+) -> anyhow::Result<Vec<配方名片>> {
+    let 記錄 = 安裝記錄::讀取或新建(&工作場地.join(安裝記錄文件名))?;
+    let mut 衆配方 = vec![];
+    for 名字 in 記錄.衆配方.keys() {
+        let 配方 = 名字.parse::<配方名片>()?;
+        let 包 = 配方包 {
+            配方: 配方.clone(),
+            倉庫域名: None,
+        };
+        if 衆本地倉庫.contains(&包.本地路徑(工作場地)) {
+            衆配方.push(配方);
+        }
+    }
+    Ok(衆配方)
+}
+
+fn 短提交(提交: &str) -> &str {
+    &提交[..提交.len().min(7)]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::download::遠端代號;
+    use crate::testing::{提交文件, 配方};
+
+    #[test]
+    fn 測試更新倉庫() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let 上游路徑 = tmp_dir.path().join("up");
+        git2::Repository::init_opts(
+            &上游路徑,
+            git2::RepositoryInitOptions::new().initial_head("main"),
+        )
+        .unwrap();
+        提交文件(&上游路徑, "foo.schema.yaml", "v1").unwrap();
+        let 本地倉庫 = tmp_dir.path().join("ws/pkg/up/rime-foo");
+        let 網址 = format!("file://{}", 上游路徑.display());
+        git2::Repository::clone(&網址, &本地倉庫).unwrap();
+        let 舊提交 = git::head_commit_id(&本地倉庫).unwrap();
+        提交文件(&上游路徑, "foo.schema.yaml", "v2").unwrap();
+        let 新提交 = 提交文件(&上游路徑, "foo.schema.yaml", "v3")
+            .unwrap()
+            .to_string();
+        let 網絡參數 = 網絡參數::from_iter(["update"]);
+        let 配方 = 配方("up/rime-foo");
+
+        assert_eq!(
+            更新倉庫(
+                &本地倉庫,
+                &配方,
+                git::SyncPolicy::Safe,
+                &網絡參數,
+                &ProgressBar::hidden()
+            )
+            .unwrap(),
+            更新結果::已更新 {
+                舊提交,
+                新提交,
+                提交數: 2
+            }
+        );
+        assert_eq!(
+            std::fs::read_to_string(本地倉庫.join("foo.schema.yaml")).unwrap(),
+            "v3"
+        );
+        assert_eq!(
+            更新倉庫(
+                &本地倉庫,
+                &配方,
+                git::SyncPolicy::Safe,
+                &網絡參數,
+                &ProgressBar::hidden()
+            )
+            .unwrap(),
+            更新結果::已是最新("main".to_owned())
+        );
+
+        // 固定在某個提交的不動
+        let 倉庫 = git2::Repository::open(&本地倉庫).unwrap();
+        let 提交 = 倉庫.head().unwrap().peel_to_commit().unwrap();
+        倉庫.set_head_detached(提交.id()).unwrap();
+        assert!(matches!(
+            更新倉庫(
+                &本地倉庫,
+                &配方,
+                git::SyncPolicy::Safe,
+                &網絡參數,
+                &ProgressBar::hidden()
+            )
+            .unwrap(),
+            更新結果::固定版本(_)
+        ));
+    }
+
+    #[test]
+    fn 測試更新跟蹤別名分支的倉庫() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let 上游路徑 = tmp_dir.path().join("up");
+        git2::Repository::init_opts(
+            &上游路徑,
+            git2::RepositoryInitOptions::new().initial_head("main"),
+        )
+        .unwrap();
+        let 舊提交 = 提交文件(&上游路徑, "foo.schema.yaml", "v1").unwrap();
+        let 本地倉庫 = tmp_dir.path().join("ws/pkg/up/rime-foo");
+        let 網址 = format!("file://{}", 上游路徑.display());
+        let 倉庫 = git2::Repository::clone(&網址, &本地倉庫).unwrap();
+        // 本地分支 dev 跟蹤遠端 upstream 的 main
+        倉庫.remote_rename(遠端代號, "upstream").unwrap();
+        倉庫
+            .branch("dev", &倉庫.find_commit(舊提交).unwrap(), false)
+            .unwrap()
+            .set_upstream(Some("upstream/main"))
+            .unwrap();
+        倉庫.set_head("refs/heads/dev").unwrap();
+        let 新提交 = 提交文件(&上游路徑, "foo.schema.yaml", "v2")
+            .unwrap()
+            .to_string();
+        let 網絡參數 = 網絡參數::from_iter(["update"]);
+        let 配方 = 配方("up/rime-foo");
+
+        assert_eq!(
+            更新倉庫(
+                &本地倉庫,
+                &配方,
+                git::SyncPolicy::Safe,
+                &網絡參數,
+                &ProgressBar::hidden()
+            )
+            .unwrap(),
+            更新結果::已更新 {
+                舊提交: 舊提交.to_string(),
+                新提交: 新提交.clone(),
+                提交數: 1
+            }
+        );
+        assert_eq!(git::head_branch(&本地倉庫).unwrap().as_deref(), Some("dev"));
+        // 本地的 main 分支不動
+        assert_eq!(倉庫.refname_to_id("refs/heads/main").unwrap(), 舊提交);
+        assert_eq!(git::head_commit_id(&本地倉庫).unwrap(), 新提交);
+    }
+
+    #[test]
+    fn 測試離線更新倉庫() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let 上游路徑 = tmp_dir.path().join("up");
+        git2::Repository::init_opts(
+            &上游路徑,
+            git2::RepositoryInitOptions::new().initial_head("main"),
+        )
+        .unwrap();
+        提交文件(&上游路徑, "foo.schema.yaml", "v1").unwrap();
+        let 本地倉庫 = tmp_dir.path().join("ws/pkg/up/rime-foo");
+        let 網址 = format!("file://{}", 上游路徑.display());
+        git2::Repository::clone(&網址, &本地倉庫).unwrap();
+        let 新提交 = 提交文件(&上游路徑, "foo.schema.yaml", "v2")
+            .unwrap()
+            .to_string();
+        // 鏡像目錄按 <域名>/<方家>/<名字>.git 存放, 本地倉庫的網址沒有域名
+        let 鏡像目錄 = tmp_dir.path().join("mirror");
+        let 鏡像 = 鏡像目錄.join("up/rime-foo.git");
+        std::fs::create_dir_all(&鏡像).unwrap();
+        git::mirror(&網址, &鏡像, &ProgressBar::hidden(), &Default::default()).unwrap();
+        std::fs::remove_dir_all(&上游路徑).unwrap();
+
+        let 配方 = 配方("up/rime-foo");
+        let 網絡參數 = 網絡參數::from_iter([
+            "update",
+            "--offline",
+            "--mirror",
+            鏡像目錄.to_str().unwrap(),
+        ]);
+        let 結果 = 更新倉庫(
+            &本地倉庫,
+            &配方,
+            git::SyncPolicy::Safe,
+            &網絡參數,
+            &ProgressBar::hidden(),
+        )
+        .unwrap();
+        assert!(matches!(結果, 更新結果::已更新 { 新提交: 提交, .. } if 提交 == 新提交));
+        // 遠端仍是原來的網址
+        assert_eq!(git::remote_url(&本地倉庫, 遠端代號).unwrap(), 網址);
+    }
+
+    #[test]
+    fn 測試來自配方包的配方() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let 工作場地 = tmp_dir.path();
+        let mut 記錄 = 安裝記錄::default();
+        for source in [
+            "luna-pinyin",
+            "emoji:customize",
+            "emoji",
+            "file:///src/rime-foo",
+        ] {
+            記錄.記錄(&source.parse().unwrap(), 工作場地, &[]).unwrap();
+        }
+        記錄.寫入(&工作場地.join(安裝記錄文件名)).unwrap();
+        let 衆配方 = 來自配方包的配方(
+            工作場地,
+            &[
+                工作場地.join("pkg/rime/rime-emoji"),
+                工作場地.join("pkg/src/rime-foo"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            衆配方.iter().map(配方名片::to_string).collect::<Vec<_>>(),
+            [
+                "file:///src/rime-foo",
+                "rime/rime-emoji",
+                "rime/rime-emoji:customize"
+            ]
+        );
+    }
+}